@@ -0,0 +1,555 @@
+use bevy::prelude::*;
+use rand::{Rng, RngCore};
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use crate::combat::{battle_outcome, CombatState, CombatStats, Enemy, FightEvent, PartyCombatant};
+use crate::combat::damage::CombatRng;
+use crate::combat::enemies::Inflicts;
+use crate::combat::log::CombatMessage;
+use crate::combat::skills::{Effect, Element, KnownSkills, SkillDatabase, SkillDef, Skills, SkillTarget};
+use crate::combat::status::{Status, StatusEffect, StatusEffects};
+use crate::combat::turns::TurnQueue;
+use crate::core::transition::create_fadeout;
+
+/// Defensive enemies guard once their health falls below this fraction
+const GUARD_BELOW : f32 = 0.4;
+
+/// Healers look after allies whose health is below this fraction
+const HEAL_BELOW : f32 = 0.5;
+
+/// Cowards try to run away once their health falls below this fraction
+const FLEE_BELOW : f32 = 0.5;
+
+/// From 0 (never) to 1 (always)
+const FLEE_CHANCE : f64 = 0.5;
+
+// Data
+// =========================================================================
+
+/// How an enemy decides what to do on its turn
+#[derive(Component, Deserialize, Debug, Clone, Default)]
+pub enum EnemyBehaviour {
+	/// Always attacks one of the party
+	#[default]
+	Aggressive,
+	/// Attacks, but guards once badly hurt
+	Defensive,
+	/// Heals hurt allies, and attacks when nobody needs it
+	Healer,
+	/// Uses its skills whenever it has the MP, and attacks otherwise
+	Caster,
+	/// Attacks, but tries to run away once badly hurt
+	Coward,
+	/// Works through a set pattern of moves, moving on to the next phase as
+	/// its health falls
+	Boss (Vec<BossPhase>),
+}
+
+/// ```ron
+/// (
+///     below: 0.5,
+///     pattern: [Skill("cleave"), Guard, Attack],
+///     message: Some("The Goblin Chief flies into a rage!"),
+/// )
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+pub struct BossPhase {
+	/// The phase starts once the boss's health falls to this fraction
+	pub below : f32,
+	/// Moves used one after another, starting over at the end
+	pub pattern : Vec<BossMove>,
+	/// Said in the battle log as the phase starts
+	#[serde(default)]
+	pub message : Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum BossMove {
+	Attack,
+	Guard,
+	/// Uses the skill with this id, or attacks if it can't
+	Skill (String),
+}
+
+/// What an enemy decided to do with its turn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+	Attack (Entity),
+	Skill {
+		id : String,
+		targets : Vec<Entity>,
+	},
+	Guard,
+	Flee,
+}
+
+/// A combatant as the AI sees them
+#[derive(Debug, Clone)]
+pub struct Combatant {
+	pub id : Entity,
+	pub stats : CombatStats,
+}
+
+/// Everything an enemy knows when deciding its turn. Built from the battle,
+/// but can just as well be made up by hand to try out a behaviour.
+pub struct Snapshot<'a> {
+	pub actor : Combatant,
+	/// Everyone on the actor's side still standing, the actor included
+	pub allies : Vec<Combatant>,
+	/// Everyone on the other side still standing
+	pub foes : Vec<Combatant>,
+	/// The skills the actor knows, by id
+	pub skills : Vec<(&'a str, &'a SkillDef)>,
+}
+
+// Components
+// =========================================================================
+
+/// What an enemy remembers from one turn to the next
+#[derive(Component, Debug, Clone, Default)]
+pub struct AiMemory {
+	/// The boss phase the enemy is in
+	pub phase : usize,
+	/// Turns taken since the phase started
+	pub turns : usize,
+}
+
+// Decisions
+// =========================================================================
+
+impl EnemyBehaviour {
+	/// Picks the actor's next action, or `None` if there's nobody to act
+	/// against
+	pub fn decide (
+		&self,
+		snapshot : &Snapshot,
+		memory : &mut AiMemory,
+		rng : &mut dyn RngCore,
+	) -> Option<Action> {
+		let health = snapshot.actor.stats.health_left();
+
+		match self {
+			EnemyBehaviour::Defensive if health < GUARD_BELOW => Some(Action::Guard),
+			EnemyBehaviour::Coward if health < FLEE_BELOW => Some(Action::Flee),
+			EnemyBehaviour::Healer => snapshot.heal().or_else(|| snapshot.attack(rng)),
+			EnemyBehaviour::Caster => snapshot.cast_any(rng).or_else(|| snapshot.attack(rng)),
+			EnemyBehaviour::Boss(phases) => {
+				// Phases only ever move forward, even if the boss is healed
+				let phase = phases
+					.iter()
+					.rposition(|phase| health <= phase.below)
+					.unwrap_or(0)
+					.max(memory.phase);
+
+				if phase != memory.phase {
+					memory.phase = phase;
+					memory.turns = 0;
+				}
+
+				let pattern = phases.get(phase).map_or(&[][..], |phase| &phase.pattern[..]);
+				if pattern.is_empty() { return snapshot.attack(rng); }
+
+				let next = &pattern[memory.turns % pattern.len()];
+				memory.turns += 1;
+
+				match next {
+					BossMove::Attack => snapshot.attack(rng),
+					BossMove::Guard => Some(Action::Guard),
+					BossMove::Skill(id) => snapshot.cast(id, rng).or_else(|| snapshot.attack(rng)),
+				}
+			}
+			EnemyBehaviour::Aggressive
+			| EnemyBehaviour::Defensive
+			| EnemyBehaviour::Coward => snapshot.attack(rng),
+		}
+	}
+}
+
+impl Snapshot<'_> {
+	/// A basic attack on any one foe
+	fn attack (&self, rng : &mut dyn RngCore) -> Option<Action> {
+		self.foes.choose(rng).map(|foe| Action::Attack(foe.id))
+	}
+
+	/// Uses the skill if the actor has the MP for it and anyone to use it on
+	fn cast (&self, id : &str, rng : &mut dyn RngCore) -> Option<Action> {
+		let (id, def) = self.skills
+			.iter()
+			.find(|(known, def)| *known == id && self.actor.stats.mp >= def.cost)?;
+
+		let targets = self.targets(def, rng);
+		if targets.is_empty() { return None; }
+
+		Some(Action::Skill { id: id.to_string(), targets })
+	}
+
+	/// Any one of the actor's usable skills that does more than heal
+	fn cast_any (&self, rng : &mut dyn RngCore) -> Option<Action> {
+		let usable : Vec<&str> = self.skills
+			.iter()
+			.filter(|(_, def)| def.effect != Effect::Heal && self.actor.stats.mp >= def.cost)
+			.map(|(id, _)| *id)
+			.collect();
+
+		let id = usable.choose(rng)?;
+		self.cast(id, rng)
+	}
+
+	/// Uses a healing skill, if any ally it would reach is badly hurt
+	fn heal (&self) -> Option<Action> {
+		self.skills
+			.iter()
+			.filter(|(_, def)| def.effect == Effect::Heal && self.actor.stats.mp >= def.cost)
+			.find_map(|(id, def)| {
+				let targets = self.ally_targets(def.target);
+				let hurt = self.allies
+					.iter()
+					.any(|ally| targets.contains(&ally.id) && ally.stats.health_left() < HEAL_BELOW);
+
+				hurt.then(|| Action::Skill { id: id.to_string(), targets })
+			})
+	}
+
+	/// Who a skill would be used on, seen from the actor's side
+	fn targets (&self, def : &SkillDef, rng : &mut dyn RngCore) -> Vec<Entity> {
+		match def.target {
+			SkillTarget::Single => self.foes.choose(rng).map(|foe| foe.id).into_iter().collect(),
+			SkillTarget::All => self.foes.iter().map(|foe| foe.id).collect(),
+			target => self.ally_targets(target),
+		}
+	}
+
+	fn ally_targets (&self, target : SkillTarget) -> Vec<Entity> {
+		match target {
+			SkillTarget::Caster => vec![self.actor.id],
+			SkillTarget::Ally => self.allies
+				.iter()
+				.min_by(|a, b| a.stats.health_left().total_cmp(&b.stats.health_left()))
+				.map(|ally| ally.id)
+				.into_iter()
+				.collect(),
+			SkillTarget::Party => self.allies.iter().map(|ally| ally.id).collect(),
+			SkillTarget::Single | SkillTarget::All => Vec::new(),
+		}
+	}
+}
+
+// Systems
+// =========================================================================
+
+/// Has whoever's turn it is decide what to do, then does it
+#[allow(clippy::too_many_arguments)]
+pub fn process_enemy_turn (
+	mut commands : Commands,
+	mut combat_state : ResMut<State<CombatState>>,
+	mut fight_event : EventWriter<FightEvent>,
+	mut messages : EventWriter<CombatMessage>,
+	turns : Res<TurnQueue>,
+	mut rng : ResMut<CombatRng>,
+	skills : Res<Skills>,
+	databases : Res<Assets<SkillDatabase>>,
+	mut enemy_query : Query<(
+		Entity,
+		&Name,
+		&mut CombatStats,
+		&EnemyBehaviour,
+		&mut AiMemory,
+		&mut StatusEffects,
+		Option<&KnownSkills>,
+		Option<&Inflicts>,
+	), (With<Enemy>, Without<PartyCombatant>)>,
+	party_query : Query<(Entity, &CombatStats), (With<PartyCombatant>, Without<Enemy>)>,
+) {
+	let allies : Vec<Combatant> = enemy_query
+		.iter()
+		.filter(|(_, _, stats, ..)| stats.health > 0)
+		.map(|(id, _, stats, ..)| Combatant { id, stats: stats.clone() })
+		.collect();
+
+	// Enemies already defeated still give their rewards if the rest run
+	let defeated_any = enemy_query.iter().any(|(_, _, stats, ..)| stats.health == 0);
+
+	let foes : Vec<Combatant> = party_query
+		.iter()
+		.filter(|(_, stats)| stats.health > 0)
+		.map(|(id, stats)| Combatant { id, stats: stats.clone() })
+		.collect();
+
+	let database = databases.get(&skills.0);
+	let actor = turns.current.and_then(|current| allies.iter().find(|ally| ally.id == current)).cloned();
+	let Some(actor) = actor else {
+		combat_state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+		return;
+	};
+
+	let Ok((id, name, mut stats, behaviour, mut memory, mut statuses, known, inflicts)) = enemy_query.get_mut(actor.id) else {
+		combat_state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+		return;
+	};
+
+	let known_skills = known
+		.zip(database)
+		.map(|(known, database)| known.0
+			.iter()
+			.filter_map(|id| database.0.get_key_value(id))
+			.map(|(id, def)| (id.as_str(), def))
+			.collect())
+		.unwrap_or_default();
+
+	let snapshot = Snapshot { actor, allies, foes, skills: known_skills };
+	let phase = memory.phase;
+	let action = behaviour.decide(&snapshot, &mut memory, &mut rng.0);
+
+	if memory.phase != phase {
+		if let EnemyBehaviour::Boss(phases) = behaviour {
+			if let Some(message) = phases.get(memory.phase).and_then(|phase| phase.message.clone()) {
+				messages.send(CombatMessage(message));
+			}
+		}
+	}
+
+	match action {
+		Some(Action::Attack(target)) => {
+			let status = inflicts
+				.filter(|inflicts| rng.0.gen_bool(inflicts.chance))
+				.map(|inflicts| inflicts.effect);
+
+			messages.send(CombatMessage(format!("{name} attacks!")));
+			fight_event.send(FightEvent {
+				attacker: id,
+				target,
+				amount: stats.attack,
+				effect: Effect::Damage(Element::Physical),
+				status,
+				next_state: CombatState::EnemyAttack,
+			});
+
+			combat_state.set(CombatState::EnemyTurn(true)).expect("Fail mark enemy state");
+		}
+		Some(Action::Skill { id : skill, targets }) => {
+			let Some(def) = database.and_then(|database| database.0.get(&skill)) else {
+				combat_state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+				return;
+			};
+			stats.mp -= def.cost;
+
+			messages.send(CombatMessage(format!("{name} uses {}!", def.name)));
+			for target in targets {
+				fight_event.send(FightEvent {
+					attacker: id,
+					target,
+					amount: def.power,
+					effect: def.effect,
+					status: def.status,
+					next_state: CombatState::EnemyAttack,
+				});
+			}
+
+			combat_state.set(CombatState::EnemyTurn(true)).expect("Fail mark enemy state");
+		}
+		Some(Action::Guard) => {
+			// Lasts until the enemy's next turn starts
+			let guard = StatusEffect { status: Status::Guard, turns: 1 };
+			statuses.add(guard, &mut stats);
+
+			messages.send(CombatMessage(guard.status.message(name)));
+			combat_state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+		}
+		Some(Action::Flee) => {
+			if !rng.0.gen_bool(FLEE_CHANCE) {
+				messages.send(CombatMessage(format!("{name} tries to run, but can't get away!")));
+				combat_state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+				return;
+			}
+
+			messages.send(CombatMessage(format!("{name} runs away!")));
+			commands.entity(id).despawn_recursive();
+
+			let outcome = battle_outcome(
+				snapshot.allies
+					.iter()
+					.filter(|ally| ally.id != id)
+					.map(|ally| (ally.stats.health, true))
+					.chain(snapshot.foes.iter().map(|foe| (foe.stats.health, false))),
+			);
+
+			match outcome {
+				// Nothing was won if every enemy ran
+				Some(CombatState::Success) if !defeated_any => {
+					create_fadeout(
+						&mut commands,
+						None,
+					);
+
+					combat_state.set(CombatState::Fled).expect("Failed to set fled state");
+				}
+				Some(outcome) => {
+					combat_state.set(outcome).expect("Failed to set exit state");
+				}
+				None => {
+					combat_state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+				}
+			}
+		}
+		None => {
+			combat_state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::SeedableRng;
+	use rand::rngs::StdRng;
+	use super::*;
+
+	const FOE : Entity = Entity::from_raw(100);
+
+	fn combatant (id : u32, health : isize, mp : isize) -> Combatant {
+		Combatant {
+			id: Entity::from_raw(id),
+			stats: CombatStats { max_health: 10, health, attack: 1, defence: 1, speed: 1, max_mp: 10, mp },
+		}
+	}
+
+	fn skill (cost : isize, effect : Effect, target : SkillTarget) -> SkillDef {
+		SkillDef { name: "Skill".to_string(), cost, power: 5, effect, target, status: None }
+	}
+
+	/// The actor alone against one foe
+	fn snapshot<'a> (actor : Combatant, skills : Vec<(&'a str, &'a SkillDef)>) -> Snapshot<'a> {
+		Snapshot {
+			allies: vec![actor.clone()],
+			actor,
+			foes: vec![Combatant { id: FOE, ..combatant(0, 10, 0) }],
+			skills,
+		}
+	}
+
+	fn decide (behaviour : &EnemyBehaviour, snapshot : &Snapshot, memory : &mut AiMemory) -> Option<Action> {
+		behaviour.decide(snapshot, memory, &mut StdRng::seed_from_u64(0))
+	}
+
+	// Behaviours
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn aggressive_attacks () {
+		let snapshot = snapshot(combatant(0, 1, 10), Vec::new());
+		assert_eq!(decide(&EnemyBehaviour::Aggressive, &snapshot, &mut default()), Some(Action::Attack(FOE)));
+	}
+
+	#[test]
+	fn nobody_to_act_against () {
+		let mut snapshot = snapshot(combatant(0, 10, 10), Vec::new());
+		snapshot.foes.clear();
+		assert_eq!(decide(&EnemyBehaviour::Aggressive, &snapshot, &mut default()), None);
+	}
+
+	#[test]
+	fn defensive_guards_when_badly_hurt () {
+		let hurt = snapshot(combatant(0, 3, 10), Vec::new());
+		let fine = snapshot(combatant(0, 4, 10), Vec::new());
+
+		assert_eq!(decide(&EnemyBehaviour::Defensive, &hurt, &mut default()), Some(Action::Guard));
+		assert_eq!(decide(&EnemyBehaviour::Defensive, &fine, &mut default()), Some(Action::Attack(FOE)));
+	}
+
+	#[test]
+	fn healer_heals_hurt_allies () {
+		let heal = skill(2, Effect::Heal, SkillTarget::Ally);
+		let actor = combatant(0, 10, 10);
+
+		let mut hurt = snapshot(actor.clone(), vec![("heal", &heal)]);
+		hurt.allies.push(combatant(1, 4, 0));
+
+		assert_eq!(
+			decide(&EnemyBehaviour::Healer, &hurt, &mut default()),
+			Some(Action::Skill { id: "heal".to_string(), targets: vec![Entity::from_raw(1)] }),
+		);
+
+		let mut fine = snapshot(actor, vec![("heal", &heal)]);
+		fine.allies.push(combatant(1, 5, 0));
+
+		assert_eq!(decide(&EnemyBehaviour::Healer, &fine, &mut default()), Some(Action::Attack(FOE)));
+	}
+
+	#[test]
+	fn healer_attacks_without_the_mp () {
+		let heal = skill(2, Effect::Heal, SkillTarget::Ally);
+		let mut snapshot = snapshot(combatant(0, 10, 1), vec![("heal", &heal)]);
+		snapshot.allies.push(combatant(1, 1, 0));
+
+		assert_eq!(decide(&EnemyBehaviour::Healer, &snapshot, &mut default()), Some(Action::Attack(FOE)));
+	}
+
+	#[test]
+	fn caster_skips_heals_and_skills_it_cannot_afford () {
+		let heal = skill(1, Effect::Heal, SkillTarget::Caster);
+		let fire = skill(3, Effect::Damage(Element::Fire), SkillTarget::Single);
+		let meteor = skill(20, Effect::Damage(Element::Fire), SkillTarget::All);
+		let skills = vec![("heal", &heal), ("fire", &fire), ("meteor", &meteor)];
+
+		let usable = snapshot(combatant(0, 1, 10), skills.clone());
+		for seed in 0..50 {
+			let action = EnemyBehaviour::Caster.decide(&usable, &mut default(), &mut StdRng::seed_from_u64(seed));
+			assert_eq!(action, Some(Action::Skill { id: "fire".to_string(), targets: vec![FOE] }));
+		}
+
+		let out_of_mp = snapshot(combatant(0, 1, 2), skills);
+		assert_eq!(decide(&EnemyBehaviour::Caster, &out_of_mp, &mut default()), Some(Action::Attack(FOE)));
+	}
+
+	#[test]
+	fn coward_flees_when_badly_hurt () {
+		let hurt = snapshot(combatant(0, 4, 10), Vec::new());
+		let fine = snapshot(combatant(0, 5, 10), Vec::new());
+
+		assert_eq!(decide(&EnemyBehaviour::Coward, &hurt, &mut default()), Some(Action::Flee));
+		assert_eq!(decide(&EnemyBehaviour::Coward, &fine, &mut default()), Some(Action::Attack(FOE)));
+	}
+
+	// Bosses
+	// -------------------------------------------------------------------------
+
+	fn boss () -> EnemyBehaviour {
+		EnemyBehaviour::Boss(vec![
+			BossPhase { below: 1., pattern: vec![BossMove::Attack, BossMove::Guard], message: None },
+			BossPhase { below: 0.5, pattern: vec![BossMove::Skill("cleave".to_string())], message: None },
+		])
+	}
+
+	#[test]
+	fn boss_pattern_wraps () {
+		let boss = boss();
+		let snapshot = snapshot(combatant(0, 10, 10), Vec::new());
+		let mut memory = AiMemory::default();
+
+		let moves : Vec<_> = (0..3).map(|_| decide(&boss, &snapshot, &mut memory)).collect();
+		assert_eq!(moves, vec![Some(Action::Attack(FOE)), Some(Action::Guard), Some(Action::Attack(FOE))]);
+		assert_eq!(memory.phase, 0);
+	}
+
+	#[test]
+	fn boss_phases_only_move_forward () {
+		let boss = boss();
+		let cleave = skill(3, Effect::Damage(Element::Physical), SkillTarget::All);
+		let cleave_action = Some(Action::Skill { id: "cleave".to_string(), targets: vec![FOE] });
+		let mut memory = AiMemory::default();
+
+		decide(&boss, &snapshot(combatant(0, 10, 10), Vec::new()), &mut memory);
+
+		let hurt = snapshot(combatant(0, 5, 10), vec![("cleave", &cleave)]);
+		assert_eq!(decide(&boss, &hurt, &mut memory), cleave_action);
+		assert_eq!((memory.phase, memory.turns), (1, 1));
+
+		// Healing back up doesn't return to the first phase
+		let healed = snapshot(combatant(0, 10, 10), vec![("cleave", &cleave)]);
+		assert_eq!(decide(&boss, &healed, &mut memory), cleave_action);
+		assert_eq!(memory.phase, 1);
+
+		// Moves it can't make fall back to attacking
+		let out_of_mp = snapshot(combatant(0, 10, 0), vec![("cleave", &cleave)]);
+		assert_eq!(decide(&boss, &out_of_mp, &mut memory), Some(Action::Attack(FOE)));
+	}
+}
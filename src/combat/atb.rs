@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use rand::{Rng, RngCore};
+use crate::combat::{CombatState, CombatStats};
+use crate::combat::damage::CombatRng;
+use crate::combat::turns::TurnQueue;
+use crate::consts::WHITE_ISH;
+use crate::core::settings::{BattleMode, Settings};
+use crate::{PIXEL_SIZE, TILE_SIZE};
+
+/// How much of the gauge each point of speed fills in a second
+const FILL_RATE : f32 = 0.06;
+
+/// The most a gauge can start a battle filled, so not everyone starts at
+/// the same time
+const MAX_HEAD_START : f32 = 0.3;
+
+// Components
+// =========================================================================
+
+/// How close a combatant is to their next action in an active time battle,
+/// from 0 (just acted) to 1 (ready)
+#[derive(Component)]
+pub struct AtbGauge (pub f32);
+
+/// The filled part of a gauge bar, a child of the combatant
+#[derive(Component)]
+pub struct AtbGaugeFill;
+
+// Systems
+// =========================================================================
+
+/// Gives every combatant a gauge and a bar to show it when battles are in
+/// active time
+pub fn attach_atb_gauges (
+	mut commands : Commands,
+	settings : Res<Settings>,
+	mut rng : ResMut<CombatRng>,
+	query : Query<Entity, (With<CombatStats>, Without<AtbGauge>)>,
+) {
+	if settings.battle_mode != BattleMode::Active { return; }
+
+	let width = TILE_SIZE * 0.8;
+	let height = PIXEL_SIZE * 2.;
+
+	for id in &query {
+		let track = commands.spawn(SpriteBundle {
+			sprite: Sprite {
+				color: WHITE_ISH,
+				custom_size: Some(Vec2::new(width, height)),
+				..default()
+			},
+			transform: Transform::from_xyz(0., -TILE_SIZE * 0.6, 1.),
+			..default()
+		}).id();
+
+		let fill = commands.spawn((
+			AtbGaugeFill,
+			SpriteBundle {
+				sprite: Sprite {
+					color: Color::hex("6ED57E").unwrap(),
+					custom_size: Some(Vec2::new(width, height)),
+					anchor: Anchor::CenterLeft,
+					..default()
+				},
+				transform: Transform::from_xyz(-width * 0.5, -TILE_SIZE * 0.6, 2.),
+				..default()
+			},
+		)).id();
+
+		commands
+			.entity(id)
+			.insert(AtbGauge(head_start(&mut rng.0)))
+			.push_children(&[track, fill]);
+	}
+}
+
+/// Fills gauges by speed, lining combatants up in the turn queue as their
+/// gauges fill. Time stands still while attacks play out.
+pub fn fill_atb_gauges (
+	settings : Res<Settings>,
+	time : Res<Time>,
+	state : Res<State<CombatState>>,
+	mut queue : ResMut<TurnQueue>,
+	mut query : Query<(Entity, &CombatStats, &mut AtbGauge)>,
+) {
+	if settings.battle_mode != BattleMode::Active { return; }
+
+	for (id, stats, mut gauge) in &mut query {
+		fill_gauge(id, stats, &mut gauge, &mut queue, state.current(), time.delta_seconds());
+	}
+}
+
+pub fn update_atb_bars (
+	gauge_query : Query<&AtbGauge>,
+	mut bar_query : Query<(&Parent, &mut Transform), With<AtbGaugeFill>>,
+) {
+	for (parent, mut transform) in &mut bar_query {
+		if let Ok(gauge) = gauge_query.get(parent.get()) {
+			transform.scale.x = gauge.0.clamp(0., 1.);
+		}
+	}
+}
+
+// Helpers
+// =========================================================================
+
+/// How full a gauge starts the battle
+fn head_start (rng : &mut dyn RngCore) -> f32 {
+	rng.gen_range(0. ..MAX_HEAD_START)
+}
+
+/// Moves one combatant's gauge on by `seconds`, adding them to the queue
+/// once it's full
+fn fill_gauge (
+	id : Entity,
+	stats : &CombatStats,
+	gauge : &mut AtbGauge,
+	queue : &mut TurnQueue,
+	state : &CombatState,
+	seconds : f32,
+) {
+	// The gauge empties as the combatant acts
+	if queue.current == Some(id) || stats.health == 0 {
+		gauge.0 = 0.;
+		return;
+	}
+
+	let running = matches!(state, CombatState::NextTurn | CombatState::PlayerTurn);
+	if !running || queue.upcoming.contains(&id) { return; }
+
+	gauge.0 += stats.speed.max(1) as f32 * FILL_RATE * seconds;
+
+	if gauge.0 >= 1. {
+		gauge.0 = 1.;
+		queue.upcoming.push_back(id);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::SeedableRng;
+	use rand::rngs::StdRng;
+	use super::*;
+
+	fn stats (speed : isize) -> CombatStats {
+		CombatStats { max_health: 10, health: 10, attack: 1, defence: 1, speed, max_mp: 0, mp: 0 }
+	}
+
+	/// Fills a gauge from `start` for `seconds`, returning where it ended up
+	fn fill (speed : isize, start : f32, seconds : f32) -> f32 {
+		let mut gauge = AtbGauge(start);
+		fill_gauge(Entity::from_raw(0), &stats(speed), &mut gauge, &mut TurnQueue::default(), &CombatState::NextTurn, seconds);
+		gauge.0
+	}
+
+	// Filling
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn gauges_fill_by_speed () {
+		assert!((fill(10, 0., 1.) - 10. * FILL_RATE).abs() < 1e-6);
+		assert!((fill(3, 0., 2.) - fill(6, 0., 1.)).abs() < 1e-6);
+		assert!((fill(5, 0.2, 1.) - 0.2 - fill(5, 0., 1.)).abs() < 1e-6);
+	}
+
+	#[test]
+	fn slow_gauges_still_fill () {
+		assert_eq!(fill(0, 0., 1.), fill(1, 0., 1.));
+		assert_eq!(fill(-4, 0., 1.), fill(1, 0., 1.));
+	}
+
+	#[test]
+	fn full_gauges_join_the_queue () {
+		let id = Entity::from_raw(0);
+		let mut gauge = AtbGauge(0.4);
+		let mut queue = TurnQueue::default();
+
+		fill_gauge(id, &stats(10), &mut gauge, &mut queue, &CombatState::NextTurn, 0.99);
+		assert!(gauge.0 < 1.);
+		assert!(queue.upcoming.is_empty());
+
+		fill_gauge(id, &stats(10), &mut gauge, &mut queue, &CombatState::NextTurn, 0.02);
+		assert_eq!(gauge.0, 1.);
+		assert_eq!(queue.upcoming, [id]);
+
+		// Waiting in the queue doesn't add them again
+		fill_gauge(id, &stats(10), &mut gauge, &mut queue, &CombatState::PlayerTurn, 1.);
+		assert_eq!(queue.upcoming, [id]);
+	}
+
+	#[test]
+	fn gauges_only_fill_between_actions () {
+		let frozen = [
+			CombatState::PlayerAttack,
+			CombatState::EnemyTurn(false),
+			CombatState::EnemyAttack,
+			CombatState::Success,
+			CombatState::Defeat,
+		];
+
+		for state in frozen {
+			let mut gauge = AtbGauge(0.5);
+			fill_gauge(Entity::from_raw(0), &stats(10), &mut gauge, &mut TurnQueue::default(), &state, 1.);
+			assert_eq!(gauge.0, 0.5, "gauge filled during {state:?}");
+		}
+
+		assert!(fill(10, 0.5, 1.) > 0.5);
+	}
+
+	// Emptying
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn acting_empties_the_gauge () {
+		let id = Entity::from_raw(0);
+		let mut gauge = AtbGauge(1.);
+		let mut queue = TurnQueue { current: Some(id), ..default() };
+
+		fill_gauge(id, &stats(10), &mut gauge, &mut queue, &CombatState::PlayerTurn, 1.);
+
+		assert_eq!(gauge.0, 0.);
+	}
+
+	#[test]
+	fn knocked_out_gauges_stay_empty () {
+		let id = Entity::from_raw(0);
+		let mut gauge = AtbGauge(0.8);
+		let stats = CombatStats { health: 0, ..stats(10) };
+
+		// Even while attacks play out
+		fill_gauge(id, &stats, &mut gauge, &mut TurnQueue::default(), &CombatState::EnemyAttack, 1.);
+		assert_eq!(gauge.0, 0.);
+
+		fill_gauge(id, &stats, &mut gauge, &mut TurnQueue::default(), &CombatState::NextTurn, 10.);
+		assert_eq!(gauge.0, 0.);
+	}
+
+	// Head start
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn seeded_head_starts_repeat () {
+		let roll = |seed| {
+			let mut rng = CombatRng(StdRng::seed_from_u64(seed));
+			[head_start(&mut rng.0), head_start(&mut rng.0), head_start(&mut rng.0)]
+		};
+
+		assert_eq!(roll(3), roll(3));
+		assert_ne!(roll(3), roll(4));
+		assert!(roll(3).iter().all(|start| (0. ..MAX_HEAD_START).contains(start)));
+	}
+}
@@ -0,0 +1,310 @@
+use bevy::prelude::*;
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
+use crate::combat::CombatStats;
+use crate::combat::skills::Element;
+
+// Formula
+// =========================================================================
+
+/// An attack about to land, for working out its damage
+pub struct Attack<'a> {
+	/// Damage before defence, variance and elements
+	pub power : isize,
+	pub element : Element,
+	pub attacker : &'a CombatStats,
+}
+
+/// Whoever an attack is aimed at
+pub struct Defender<'a> {
+	pub stats : &'a CombatStats,
+	/// Elements that deal the defender extra damage
+	pub weak_to : &'a [Element],
+	/// Elements that deal the defender less damage
+	pub resists : &'a [Element],
+	/// Whether the defender is defending themselves this turn
+	pub guarding : bool,
+}
+
+/// How an attack turned out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+	Miss,
+	Damage {
+		amount : isize,
+		critical : bool,
+	},
+}
+
+/// Works out how much damage an attack deals. Every roll comes from `rng`,
+/// so a seeded generator always gives the same results.
+pub trait DamageFormula : Send + Sync + 'static {
+	fn roll (&self, attack : &Attack, defender : &Defender, rng : &mut dyn RngCore) -> Hit;
+}
+
+/// The damage rules battles use unless told otherwise.
+///
+/// Physical attacks can miss, more often the faster the defender is than
+/// the attacker, while elemental attacks always land. Damage swings a
+/// little either way, then defence is taken off unless the hit is critical.
+/// Weaknesses, resistances and guarding scale what's left, and every hit
+/// deals at least `min_damage`.
+#[derive(Debug, Clone)]
+pub struct StandardFormula {
+	/// How far damage can swing either way, as a fraction of its power
+	pub variance : f32,
+	/// From 0 (never) to 1 (always)
+	pub critical_chance : f64,
+	pub critical_multiplier : f32,
+	/// Chance of a physical attack landing between equally fast combatants
+	pub accuracy : f64,
+	/// How much less likely an attack is to land for each point of speed the
+	/// defender has over the attacker
+	pub evasion_per_speed : f64,
+	/// The lowest chance a physical attack has of landing
+	pub min_accuracy : f64,
+	pub weakness_multiplier : f32,
+	pub resistance_multiplier : f32,
+	/// Scales the damage dealt to defenders who are guarding
+	pub guard_multiplier : f32,
+	pub min_damage : isize,
+}
+
+impl Default for StandardFormula {
+	fn default () -> Self {
+		StandardFormula {
+			variance: 0.1,
+			critical_chance: 0.05,
+			critical_multiplier: 1.5,
+			accuracy: 0.95,
+			evasion_per_speed: 0.03,
+			min_accuracy: 0.5,
+			weakness_multiplier: 2.,
+			resistance_multiplier: 0.5,
+			guard_multiplier: 0.5,
+			min_damage: 1,
+		}
+	}
+}
+
+impl DamageFormula for StandardFormula {
+	fn roll (&self, attack : &Attack, defender : &Defender, rng : &mut dyn RngCore) -> Hit {
+		if attack.element == Element::Physical {
+			let speed_gap = (defender.stats.speed - attack.attacker.speed) as f64;
+			let accuracy = (self.accuracy - speed_gap * self.evasion_per_speed)
+				.clamp(self.min_accuracy.clamp(0., 1.), 1.);
+
+			if !rng.gen_bool(accuracy) { return Hit::Miss; }
+		}
+
+		let critical = rng.gen_bool(self.critical_chance.clamp(0., 1.));
+		// `gen_range` panics on an empty range, so a bad variance means none
+		let variance = self.variance.abs();
+		let swing = if variance > 0. && variance.is_finite() {
+			rng.gen_range(-variance..=variance)
+		} else {
+			0.
+		};
+		let mut damage = attack.power as f32 * (1. + swing);
+
+		// Critical hits go straight through defence
+		if critical {
+			damage *= self.critical_multiplier;
+		} else {
+			damage -= defender.stats.defence as f32;
+		}
+
+		if defender.weak_to.contains(&attack.element) {
+			damage *= self.weakness_multiplier;
+		} else if defender.resists.contains(&attack.element) {
+			damage *= self.resistance_multiplier;
+		}
+
+		if defender.guarding {
+			damage *= self.guard_multiplier;
+		}
+
+		Hit::Damage {
+			amount: (damage.round() as isize).max(self.min_damage),
+			critical,
+		}
+	}
+}
+
+// Resources
+// =========================================================================
+
+/// The damage formula battles use
+#[derive(Resource)]
+pub struct DamageRules (pub Box<dyn DamageFormula>);
+
+impl Default for DamageRules {
+	fn default () -> Self {
+		DamageRules(Box::new(StandardFormula::default()))
+	}
+}
+
+/// Where every random roll in battle comes from. Insert one made with
+/// `StdRng::seed_from_u64` to replay the same battle exactly.
+#[derive(Resource)]
+pub struct CombatRng (pub StdRng);
+
+impl Default for CombatRng {
+	fn default () -> Self {
+		CombatRng(StdRng::from_entropy())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Never misses, never crits and never swings, so only the rule being
+	/// tested changes the damage
+	fn exact () -> StandardFormula {
+		StandardFormula {
+			variance: 0.,
+			critical_chance: 0.,
+			accuracy: 1.,
+			min_accuracy: 1.,
+			..default()
+		}
+	}
+
+	fn stats (defence : isize, speed : isize) -> CombatStats {
+		CombatStats { max_health: 10, health: 10, attack: 1, defence, speed, max_mp: 0, mp: 0 }
+	}
+
+	fn roll (formula : &StandardFormula, element : Element, power : isize, defender : &Defender, seed : u64) -> Hit {
+		let attacker = stats(0, 10);
+		let attack = Attack { power, element, attacker: &attacker };
+		formula.roll(&attack, defender, &mut StdRng::seed_from_u64(seed))
+	}
+
+	fn defender (stats : &CombatStats) -> Defender<'_> {
+		Defender { stats, weak_to: &[], resists: &[], guarding: false }
+	}
+
+	fn damage (amount : isize) -> Hit {
+		Hit::Damage { amount, critical: false }
+	}
+
+	// Damage
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn defence_is_taken_off () {
+		let stats = stats(3, 10);
+		assert_eq!(roll(&exact(), Element::Physical, 10, &defender(&stats), 0), damage(7));
+	}
+
+	#[test]
+	fn damage_is_at_least_min_damage () {
+		let stats = stats(50, 10);
+		assert_eq!(roll(&exact(), Element::Physical, 10, &defender(&stats), 0), damage(1));
+
+		let formula = StandardFormula { min_damage: 3, ..exact() };
+		assert_eq!(roll(&formula, Element::Physical, 10, &defender(&stats), 0), damage(3));
+	}
+
+	#[test]
+	fn critical_hits_ignore_defence () {
+		let formula = StandardFormula { critical_chance: 1., ..exact() };
+		let stats = stats(50, 10);
+
+		assert_eq!(
+			roll(&formula, Element::Physical, 10, &defender(&stats), 0),
+			Hit::Damage { amount: 15, critical: true },
+		);
+	}
+
+	#[test]
+	fn damage_swings_within_variance () {
+		let formula = StandardFormula { variance: 0.5, ..exact() };
+		let stats = stats(0, 10);
+
+		for seed in 0..100 {
+			let Hit::Damage { amount, .. } = roll(&formula, Element::Physical, 10, &defender(&stats), seed) else {
+				panic!("exact formulas never miss");
+			};
+			assert!((5..=15).contains(&amount), "{amount} is outside the variance");
+		}
+	}
+
+	#[test]
+	fn bad_variance_does_not_panic () {
+		let stats = stats(0, 10);
+
+		let negative = StandardFormula { variance: -0.5, ..exact() };
+		let Hit::Damage { amount, .. } = roll(&negative, Element::Physical, 10, &defender(&stats), 0) else {
+			panic!("exact formulas never miss");
+		};
+		assert!((5..=15).contains(&amount));
+
+		for variance in [f32::NAN, f32::INFINITY] {
+			let formula = StandardFormula { variance, ..exact() };
+			assert_eq!(roll(&formula, Element::Physical, 10, &defender(&stats), 0), damage(10));
+		}
+	}
+
+	// Accuracy
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn faster_defenders_dodge () {
+		// 10 points faster takes the accuracy from 0.95 down to 0.65
+		let formula = StandardFormula { min_accuracy: 0., ..StandardFormula::default() };
+		let even = stats(0, 10);
+		let fast = stats(0, 20);
+		let very_fast = stats(0, 100);
+
+		let misses = |stats : &CombatStats| (0..1000)
+			.filter(|seed| roll(&formula, Element::Physical, 10, &defender(stats), *seed) == Hit::Miss)
+			.count();
+
+		assert!(misses(&even) < misses(&fast));
+		assert_eq!(misses(&very_fast), 1000);
+	}
+
+	#[test]
+	fn min_accuracy_keeps_attacks_landing () {
+		let formula = StandardFormula { accuracy: 0., min_accuracy: 1., ..exact() };
+		let stats = stats(0, 100);
+
+		for seed in 0..100 {
+			assert_ne!(roll(&formula, Element::Physical, 10, &defender(&stats), seed), Hit::Miss);
+		}
+	}
+
+	#[test]
+	fn elemental_attacks_never_miss () {
+		let formula = StandardFormula { accuracy: 0., min_accuracy: 0., ..exact() };
+		let stats = stats(0, 100);
+
+		for seed in 0..100 {
+			assert_ne!(roll(&formula, Element::Fire, 10, &defender(&stats), seed), Hit::Miss);
+		}
+	}
+
+	// Elements and guarding
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn weaknesses_and_resistances_scale_damage () {
+		let stats = stats(0, 10);
+		let weak = Defender { weak_to: &[Element::Fire], ..defender(&stats) };
+		let resists = Defender { resists: &[Element::Fire], ..defender(&stats) };
+
+		assert_eq!(roll(&exact(), Element::Fire, 10, &weak, 0), damage(20));
+		assert_eq!(roll(&exact(), Element::Fire, 10, &resists, 0), damage(5));
+		assert_eq!(roll(&exact(), Element::Ice, 10, &weak, 0), damage(10));
+	}
+
+	#[test]
+	fn guarding_scales_damage () {
+		let stats = stats(0, 10);
+		let guarding = Defender { guarding: true, ..defender(&stats) };
+
+		assert_eq!(roll(&exact(), Element::Physical, 10, &guarding, 0), damage(5));
+	}
+}
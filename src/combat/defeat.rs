@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use crate::combat::{CombatStats, PartyCombatant};
+use crate::combat::log::CombatMessage;
+use crate::combat::status::StatusEffects;
+use crate::core::settings::{DefeatMode, Settings};
+use crate::core::tilemap::{RESPAWN, TeleportEvent, Warp, WarpEvent};
+use crate::core::transition::{create_fadeout, create_set_fadeout};
+use crate::GameState;
+use crate::items::Inventory;
+use crate::save::SaveData;
+use crate::scenes::overworld::STARTING_MAP;
+
+/// How long the fallen party lingers on screen before the game moves on, in
+/// seconds
+const DEFEAT_DURATION : f32 = 2.5;
+
+/// Fraction of the party's gold lost when they wake up after being defeated
+const RESPAWN_GOLD_PENALTY : f32 = 0.5;
+
+// Resources
+// =========================================================================
+
+#[derive(Resource)]
+pub struct DefeatSequence {
+	timer : Timer,
+}
+
+impl Default for DefeatSequence {
+	fn default () -> Self {
+		DefeatSequence {
+			timer: Timer::from_seconds(DEFEAT_DURATION, TimerMode::Once),
+		}
+	}
+}
+
+// Systems
+// =========================================================================
+
+pub fn start_defeat (
+	mut sequence : ResMut<DefeatSequence>,
+	mut messages : EventWriter<CombatMessage>,
+) {
+	sequence.timer.reset();
+	messages.send(CombatMessage("The party has fallen...".to_string()));
+}
+
+/// Fades the fallen party away, then either goes to the game over screen or
+/// wakes the party up at the last healer they saved at, depending on the
+/// settings. Without a save they wake up at the starting map's `respawn`.
+#[allow(clippy::too_many_arguments)]
+pub fn play_defeat_sequence (
+	mut commands : Commands,
+	time : Res<Time>,
+	settings : Res<Settings>,
+	mut sequence : ResMut<DefeatSequence>,
+	mut inventory : ResMut<Inventory>,
+	save : Option<Res<SaveData>>,
+	mut party_query : Query<(&mut TextureAtlasSprite, &mut CombatStats, &mut StatusEffects), With<PartyCombatant>>,
+	mut messages : EventWriter<CombatMessage>,
+	mut teleports : EventWriter<TeleportEvent>,
+	mut warps : EventWriter<WarpEvent>,
+) {
+	sequence.timer.tick(time.delta());
+
+	for (mut sprite, ..) in &mut party_query {
+		sprite.color.set_a(1. - sequence.timer.percent() * 0.75);
+	}
+
+	if !sequence.timer.just_finished() { return; }
+
+	match settings.defeat_mode {
+		DefeatMode::GameOver => create_set_fadeout(&mut commands, GameState::GameOver),
+		DefeatMode::Respawn => {
+			let lost = (inventory.gold as f32 * RESPAWN_GOLD_PENALTY) as usize;
+			inventory.gold -= lost;
+			let place = if save.is_some() { "at the healer" } else { "where your journey began" };
+			messages.send(CombatMessage(format!("You come round {place}, {lost} gold lighter.")));
+
+			// The party's health is kept as they leave the battle, so they're
+			// healed here rather than in the overworld
+			for (_, mut stats, mut statuses) in &mut party_query {
+				statuses.clear(&mut stats);
+				stats.health = stats.max_health;
+				stats.mp = stats.max_mp;
+			}
+
+			// Without a save, the party wakes up on the starting map
+			match save {
+				Some(save) => teleports.send(TeleportEvent {
+					map: save.map.clone(),
+					position: save.position,
+				}),
+				None => warps.send(WarpEvent(Warp {
+					map: STARTING_MAP.to_string(),
+					spawn: RESPAWN.to_string(),
+				})),
+			}
+
+			create_fadeout(
+				&mut commands,
+				None,
+			);
+		}
+	}
+}
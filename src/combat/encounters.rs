@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use rand::{Rng, RngCore};
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+// Assets
+// =========================================================================
+
+/// Weighted groups of enemies for each kind of area, keyed by table name.
+/// Maps point their encounter tiles at a table. Loaded from
+/// `*.encounters.ron` files.
+///
+/// ```ron
+/// {
+///     "forest": [
+///         (enemies: ["goblin"], weight: 3, levels: (1, 2)),
+///         (enemies: ["bat"], weight: 1, levels: (2, 3)),
+///         (enemies: ["troll"], weight: 1, levels: (5, 5), inescapable: true),
+///     ],
+/// }
+/// ```
+#[derive(TypeUuid, Deserialize, Debug, Default)]
+#[uuid = "c51b2e07-94d3-4c8a-b6f1-0a7e3d92c418"]
+#[serde(transparent)]
+pub struct EncounterTables (pub HashMap<String, Vec<EncounterGroup>>);
+
+impl EncounterTables {
+	/// Picks a group from the named table, weighted by how common each group
+	/// is, and a level for each of its enemies
+	pub fn roll (&self, table : &str, rng : &mut dyn RngCore) -> Option<Encounter> {
+		let group = self.0.get(table)?.choose_weighted(rng, |group| group.weight).ok()?;
+		let (min, max) = group.levels;
+
+		Some(Encounter {
+			enemies: group.enemies
+				.iter()
+				.map(|id| EncounterEnemy {
+					id: id.clone(),
+					level: rng.gen_range(min..=max.max(min)),
+				})
+				.collect(),
+			inescapable: group.inescapable,
+		})
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EncounterGroup {
+	/// Ids from the enemy database
+	pub enemies : Vec<String>,
+	/// How common the group is compared to the rest of its table
+	pub weight : u32,
+	/// The lowest and highest level the group's enemies can be
+	pub levels : (u32, u32),
+	/// Stops the party running from the battle, for bosses
+	#[serde(default)]
+	pub inescapable : bool,
+}
+
+// Resources
+// =========================================================================
+
+#[derive(Resource)]
+pub struct Encounters (pub Handle<EncounterTables>);
+
+/// The enemies the next battle is fought against
+#[derive(Resource, Debug, Clone)]
+pub struct Encounter {
+	pub enemies : Vec<EncounterEnemy>,
+	/// Whether the party is stuck fighting until one side wins
+	pub inescapable : bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncounterEnemy {
+	pub id : String,
+	pub level : u32,
+}
+
+// Systems
+// =========================================================================
+
+pub fn load_encounters (
+	mut commands : Commands,
+	assets : Res<AssetServer>,
+) {
+	commands.insert_resource(Encounters(assets.load("data/base.encounters.ron")));
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::SeedableRng;
+	use rand::rngs::StdRng;
+	use super::*;
+
+	fn group (enemies : &[&str], weight : u32, levels : (u32, u32)) -> EncounterGroup {
+		EncounterGroup {
+			enemies: enemies.iter().map(|id| id.to_string()).collect(),
+			weight,
+			levels,
+			inescapable: false,
+		}
+	}
+
+	fn tables (groups : Vec<EncounterGroup>) -> EncounterTables {
+		EncounterTables(HashMap::from([("forest".to_string(), groups)]))
+	}
+
+	fn roll (tables : &EncounterTables, table : &str, seed : u64) -> Option<Encounter> {
+		tables.roll(table, &mut StdRng::seed_from_u64(seed))
+	}
+
+	// Groups
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn unknown_tables_roll_nothing () {
+		let tables = tables(vec![group(&["goblin"], 1, (1, 1))]);
+
+		assert!(roll(&tables, "desert", 0).is_none());
+	}
+
+	#[test]
+	fn empty_tables_roll_nothing () {
+		assert!(roll(&tables(vec![]), "forest", 0).is_none());
+	}
+
+	#[test]
+	fn groups_are_picked_by_weight () {
+		let tables = tables(vec![
+			group(&["goblin"], 3, (1, 1)),
+			group(&["bat"], 1, (1, 1)),
+			group(&["troll"], 0, (1, 1)),
+		]);
+		let mut rng = StdRng::seed_from_u64(7);
+
+		let goblins = (0..4000)
+			.map(|_| tables.roll("forest", &mut rng).unwrap())
+			.inspect(|encounter| assert_ne!(encounter.enemies[0].id, "troll"))
+			.filter(|encounter| encounter.enemies[0].id == "goblin")
+			.count();
+
+		assert!((2800..3200).contains(&goblins), "{goblins} goblins in 4000 rolls");
+	}
+
+	#[test]
+	fn every_enemy_in_the_group_is_rolled () {
+		let tables = tables(vec![group(&["goblin", "bat", "goblin"], 1, (1, 1))]);
+
+		let ids : Vec<_> = roll(&tables, "forest", 0).unwrap().enemies
+			.into_iter()
+			.map(|enemy| enemy.id)
+			.collect();
+
+		assert_eq!(ids, ["goblin", "bat", "goblin"]);
+	}
+
+	#[test]
+	fn inescapable_carries_over () {
+		let boss = EncounterGroup { inescapable: true, ..group(&["troll"], 1, (5, 5)) };
+
+		assert!(roll(&tables(vec![boss]), "forest", 0).unwrap().inescapable);
+		assert!(!roll(&tables(vec![group(&["bat"], 1, (1, 1))]), "forest", 0).unwrap().inescapable);
+	}
+
+	// Levels
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn levels_stay_in_range () {
+		let tables = tables(vec![group(&["goblin"; 4], 1, (2, 4))]);
+		let mut levels = Vec::new();
+
+		for seed in 0..50 {
+			levels.extend(roll(&tables, "forest", seed).unwrap().enemies.iter().map(|enemy| enemy.level));
+		}
+
+		assert!(levels.iter().all(|level| (2..=4).contains(level)));
+		assert!([2, 3, 4].iter().all(|level| levels.contains(level)));
+	}
+
+	#[test]
+	fn backwards_levels_use_the_min () {
+		let tables = tables(vec![group(&["goblin"; 4], 1, (5, 2))]);
+
+		for seed in 0..20 {
+			let encounter = roll(&tables, "forest", seed).unwrap();
+			assert!(encounter.enemies.iter().all(|enemy| enemy.level == 5));
+		}
+	}
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+use crate::combat::{BaseStats, CombatStats, Enemy};
+use crate::combat::ai::{AiMemory, EnemyBehaviour};
+use crate::combat::skills::{Element, KnownSkills, Resistances, Weaknesses};
+use crate::combat::status::{StatusEffect, StatusEffects};
+use crate::core::assets::{spawn_tilesheet_sprite, Tilesheet};
+
+// Assets
+// =========================================================================
+
+/// Every enemy the player can fight, keyed by id. Loaded from
+/// `*.enemies.ron` files.
+///
+/// Stats are for a level 1 enemy. Every level above that adds a quarter of
+/// the base stats and half of the base XP and gold.
+///
+/// ```ron
+/// {
+///     "goblin": (
+///         name: "Goblin",
+///         sprite: 123,
+///         stats: (health: 7, attack: 2, defence: 1, speed: 5),
+///         xp: 10,
+///         gold: 5,
+///         loot: [(item: "potion", chance: 0.25)],
+///         weak_to: [Fire],
+///         resists: [Ice],
+///         inflicts: Some((effect: (status: Poison(1), turns: 5), chance: 0.3)),
+///         skills: ["fire"],
+///         behaviour: Caster,
+///     ),
+/// }
+/// ```
+#[derive(TypeUuid, Deserialize, Debug, Default)]
+#[uuid = "9d3c6a1e-5b7f-4f0a-8c2d-61e4b8a0f3d7"]
+#[serde(transparent)]
+pub struct EnemyDatabase (pub HashMap<String, EnemyDef>);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EnemyDef {
+	pub name : String,
+	/// Tilesheet index of the enemy's sprite
+	pub sprite : usize,
+	pub stats : BaseStats,
+	/// Given to the player for defeating the enemy
+	pub xp : usize,
+	#[serde(default)]
+	pub gold : usize,
+	#[serde(default)]
+	pub loot : Vec<LootDrop>,
+	/// Elements that deal the enemy double damage
+	#[serde(default)]
+	pub weak_to : Vec<Element>,
+	/// Elements that deal the enemy half damage
+	#[serde(default)]
+	pub resists : Vec<Element>,
+	#[serde(default)]
+	pub inflicts : Option<Inflicts>,
+	/// Skill ids from the skill database
+	#[serde(default)]
+	pub skills : Vec<String>,
+	#[serde(default)]
+	pub behaviour : EnemyBehaviour,
+}
+
+/// An item an enemy might drop when defeated
+#[derive(Deserialize, Debug, Clone)]
+pub struct LootDrop {
+	pub item : String,
+	/// From 0 (never) to 1 (always)
+	#[serde(deserialize_with = "chance")]
+	pub chance : f64,
+}
+
+/// A status an enemy's attacks might leave on their target
+#[derive(Component, Deserialize, Debug, Clone, Copy)]
+pub struct Inflicts {
+	pub effect : StatusEffect,
+	/// From 0 (never) to 1 (always)
+	#[serde(deserialize_with = "chance")]
+	pub chance : f64,
+}
+
+// Components
+// =========================================================================
+
+/// What the player earns for defeating an enemy
+#[derive(Component, Debug)]
+pub struct EnemyRewards {
+	pub xp : usize,
+	pub gold : usize,
+	pub loot : Vec<LootDrop>,
+}
+
+// Resources
+// =========================================================================
+
+#[derive(Resource)]
+pub struct Enemies (pub Handle<EnemyDatabase>);
+
+// Systems
+// =========================================================================
+
+pub fn load_enemies (
+	mut commands : Commands,
+	assets : Res<AssetServer>,
+) {
+	commands.insert_resource(Enemies(assets.load("data/base.enemies.ron")));
+}
+
+// Utilities
+// =========================================================================
+
+pub fn spawn_enemy (
+	commands : &mut Commands,
+	tilesheet : &Res<Tilesheet>,
+	def : &EnemyDef,
+	level : u32,
+	translation : Vec3,
+) -> Entity {
+	let extra_levels = level.saturating_sub(1) as isize;
+	let scale = |base : isize| base + base * extra_levels / 4;
+	let health = scale(def.stats.health);
+	let mp = scale(def.stats.mp);
+
+	let id = spawn_tilesheet_sprite(
+		commands,
+		tilesheet,
+		def.sprite,
+		translation,
+		None,
+	);
+
+	commands
+		.entity(id)
+		.insert(Name::new(def.name.clone()))
+		.insert(Enemy)
+		.insert(CombatStats {
+			max_health: health,
+			health,
+			attack: scale(def.stats.attack),
+			defence: scale(def.stats.defence),
+			speed: scale(def.stats.speed),
+			max_mp: mp,
+			mp,
+		})
+		.insert(EnemyRewards {
+			xp: def.xp + def.xp * extra_levels as usize / 2,
+			gold: def.gold + def.gold * extra_levels as usize / 2,
+			loot: def.loot.clone(),
+		})
+		.insert(Weaknesses(def.weak_to.clone()))
+		.insert(Resistances(def.resists.clone()))
+		.insert(StatusEffects::default())
+		.insert(KnownSkills(def.skills.clone()))
+		.insert(def.behaviour.clone())
+		.insert(AiMemory::default())
+	;
+
+	if let Some(inflicts) = def.inflicts {
+		commands.entity(id).insert(inflicts);
+	}
+
+	id
+}
+
+// Helpers
+// =========================================================================
+
+/// Reads a chance, which has to be from 0 to 1. Rolling anything else
+/// panics, NaN included, so bad data is caught as it loads instead.
+fn chance<'de, D : Deserializer<'de>> (deserializer : D) -> Result<f64, D::Error> {
+	let chance = f64::deserialize(deserializer)?;
+
+	if !(0. ..=1.).contains(&chance) {
+		return Err(D::Error::custom(format!("chance must be from 0 to 1, but was {chance}")));
+	}
+
+	Ok(chance)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn loot_chance_must_be_from_0_to_1 () {
+		let drop = |chance : &str| ron::de::from_str::<LootDrop>(&format!("(item: \"potion\", chance: {chance})"));
+
+		assert_eq!(drop("0.25").unwrap().chance, 0.25);
+		assert_eq!(drop("1").unwrap().chance, 1.);
+		assert!(drop("NaN").is_err());
+		assert!(drop("1.5").is_err());
+		assert!(drop("-0.1").is_err());
+	}
+
+	#[test]
+	fn inflict_chance_must_be_from_0_to_1 () {
+		let inflicts = |chance : &str| ron::de::from_str::<Inflicts>(&format!(
+			"(effect: (status: Poison(1), turns: 5), chance: {chance})"
+		));
+
+		assert!(inflicts("0.3").is_ok());
+		assert!(inflicts("NaN").is_err());
+		assert!(inflicts("inf").is_err());
+	}
+}
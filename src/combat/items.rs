@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use crate::combat::{CombatState, CombatStats, Enemy, FightEvent, PartyCombatant};
+use crate::combat::log::CombatMessage;
+use crate::combat::skills::{resolve_targets, SkillTarget, spawn_menu_button};
+use crate::combat::targeting::{AllyAction, AllyChosen, CombatTarget};
+use crate::combat::turns::TurnQueue;
+use crate::core::assets::PixelFont;
+use crate::items::{Inventory, ItemDatabase, Items};
+use crate::ui::Disabled;
+
+// Components
+// =========================================================================
+
+/// The list of items opened from the combat menu
+#[derive(Component)]
+pub struct ItemMenu;
+
+/// A button in the item menu, by item id
+#[derive(Component)]
+pub struct ItemButton (pub String);
+
+// Systems
+// =========================================================================
+
+/// Opens the item menu with everything in the inventory, or closes it if
+/// it's already open or another command is picked
+pub fn toggle_item_menu (
+	mut commands : Commands,
+	button_query : Query<(&Interaction, &Name), (Changed<Interaction>, With<Button>, Without<Disabled>)>,
+	mut menu_query : Query<(Entity, &mut Style), With<ItemMenu>>,
+	inventory : Res<Inventory>,
+	items : Res<Items>,
+	databases : Res<Assets<ItemDatabase>>,
+	pixel_font : Res<PixelFont>,
+) {
+	let Some(clicked) = button_query
+		.iter()
+		.find(|(interaction, _)| **interaction == Interaction::Clicked)
+		.map(|(_, name)| name.as_str()) else { return };
+
+	let Ok((menu, mut style)) = menu_query.get_single_mut() else { return };
+	commands.entity(menu).despawn_descendants();
+
+	// Picking any other command closes the menu too
+	if clicked != "item" || style.display == Display::Flex {
+		style.display = Display::None;
+		return;
+	}
+
+	let Some(database) = databases.get(&items.0) else { return };
+
+	style.display = Display::Flex;
+
+	commands.entity(menu).with_children(|parent| {
+		if inventory.items.is_empty() {
+			spawn_menu_button(parent, &pixel_font, "No items".to_string(), "6ED57E", false, ());
+		}
+
+		for (id, count) in &inventory.items {
+			let Some(def) = database.0.get(id) else {
+				warn!("Unknown item '{id}'");
+				continue;
+			};
+
+			spawn_menu_button(
+				parent,
+				&pixel_font,
+				format!("{} x{count}", def.name),
+				"6ED57E",
+				true,
+				ItemButton(id.clone()),
+			);
+		}
+	});
+}
+
+/// Uses up a clicked item and sends its effect to each of its targets.
+/// Items used on an ally wait for the player to choose one first.
+#[allow(clippy::too_many_arguments)]
+pub fn use_item (
+	mut fight_event : EventWriter<FightEvent>,
+	mut messages : EventWriter<CombatMessage>,
+	mut ally_chosen : EventReader<AllyChosen>,
+	button_query : Query<(&Interaction, &ItemButton), (Changed<Interaction>, Without<Disabled>)>,
+	party_query : Query<(Entity, &Name, &CombatStats), (With<PartyCombatant>, Without<Enemy>)>,
+	enemy_query : Query<(Entity, &CombatStats), (With<Enemy>, Without<PartyCombatant>)>,
+	mut inventory : ResMut<Inventory>,
+	turns : Res<TurnQueue>,
+	mut target : ResMut<CombatTarget>,
+	items : Res<Items>,
+	databases : Res<Assets<ItemDatabase>>,
+	state : Res<State<CombatState>>,
+) {
+	if state.current() != &CombatState::PlayerTurn { return; }
+
+	let chosen = ally_chosen.iter().find_map(|AllyChosen(action)| match action {
+		AllyAction::Item(id) => Some(id.clone()),
+		_ => None,
+	});
+
+	let clicked = button_query
+		.iter()
+		.find(|(interaction, _)| **interaction == Interaction::Clicked)
+		.map(|(_, button)| button.0.clone());
+
+	let Some(id) = chosen.clone().or(clicked) else { return };
+	let Some(def) = databases.get(&items.0).and_then(|db| db.0.get(&id)) else { return };
+	let Some(user) = turns.current else { return };
+	let Ok((_, name, _)) = party_query.get(user) else { return };
+
+	if def.target == SkillTarget::Ally && chosen.is_none() {
+		messages.send(CombatMessage(format!("Choose who to use the {} on.", def.name)));
+		target.choosing = Some(AllyAction::Item(id));
+		return;
+	}
+
+	let selected = if chosen.is_some() { target.ally } else { target.enemy };
+
+	let targets = resolve_targets(
+		def.target,
+		user,
+		selected,
+		party_query.iter().map(|(id, _, stats)| (id, stats)),
+		enemy_query.iter(),
+	);
+
+	if targets.is_empty() || !inventory.take(&id) { return; }
+
+	messages.send(CombatMessage(format!("{name} uses a {}!", def.name)));
+
+	for target in targets {
+		fight_event.send(FightEvent {
+			attacker: user,
+			target,
+			amount: def.power,
+			effect: def.effect,
+			status: def.status,
+			next_state: CombatState::PlayerAttack,
+		});
+	}
+}
+
+pub fn close_item_menu (
+	mut commands : Commands,
+	mut menu_query : Query<(Entity, &mut Style), With<ItemMenu>>,
+) {
+	for (menu, mut style) in &mut menu_query {
+		commands.entity(menu).despawn_descendants();
+		style.display = Display::None;
+	}
+}
@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+
+/// How many messages the log shows at once
+const LOG_LENGTH : usize = 6;
+
+// Events
+// =========================================================================
+
+/// A line for the battle log, like "Goblin attacks!"
+pub struct CombatMessage (pub String);
+
+// Resources
+// =========================================================================
+
+/// The latest messages of the battle, oldest first
+#[derive(Resource, Default)]
+pub struct BattleLog {
+	pub lines : VecDeque<String>,
+}
+
+// Components
+// =========================================================================
+
+#[derive(Component)]
+pub struct BattleLogText;
+
+// Systems
+// =========================================================================
+
+/// Adds new messages to the bottom of the log, scrolling the oldest off the
+/// top
+pub fn record_combat_messages (
+	mut messages : EventReader<CombatMessage>,
+	mut log : ResMut<BattleLog>,
+	mut text_query : Query<&mut Text, With<BattleLogText>>,
+) {
+	let mut changed = false;
+
+	for message in messages.iter() {
+		log.lines.push_back(message.0.clone());
+		changed = true;
+	}
+
+	if !changed { return; }
+
+	while log.lines.len() > LOG_LENGTH {
+		log.lines.pop_front();
+	}
+
+	if let Ok(mut text) = text_query.get_single_mut() {
+		text.sections[0].value = log.lines
+			.iter()
+			.map(String::as_str)
+			.collect::<Vec<_>>()
+			.join("\n");
+	}
+}
+
+pub fn clear_battle_log (
+	mut log : ResMut<BattleLog>,
+) {
+	log.lines.clear();
+}
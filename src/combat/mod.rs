@@ -0,0 +1,1091 @@
+pub mod ai;
+pub mod atb;
+pub mod damage;
+pub mod defeat;
+pub mod encounters;
+pub mod items;
+pub mod log;
+pub mod enemies;
+pub mod popups;
+pub mod results;
+pub mod skills;
+pub mod status;
+pub mod targeting;
+pub mod turns;
+
+use std::cmp::{max, min};
+use std::f32::consts::PI;
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+use crate::combat::ai::process_enemy_turn;
+use crate::combat::atb::{attach_atb_gauges, fill_atb_gauges, update_atb_bars};
+use crate::combat::damage::{Attack, CombatRng, DamageRules, Defender, Hit};
+use crate::combat::defeat::{DefeatSequence, play_defeat_sequence, start_defeat};
+use crate::combat::encounters::{Encounter, EncounterTables, load_encounters};
+use crate::combat::enemies::{Enemies, EnemyDatabase, EnemyRewards, load_enemies, spawn_enemy};
+use crate::combat::items::{close_item_menu, ItemMenu, toggle_item_menu, use_item};
+use crate::combat::log::{BattleLog, BattleLogText, clear_battle_log, CombatMessage, record_combat_messages};
+use crate::combat::popups::{animate_popups, despawn_popups, Popup, PopupEvent, spawn_popups};
+use crate::combat::results::{BattleResults, MemberResults, despawn_results, show_results, spawn_results, update_results};
+use crate::combat::skills::{close_skill_menu, Effect, Element, KnownSkills, load_skills, Resistances, SkillDatabase, SkillMenu, toggle_skill_menu, use_skill, Weaknesses};
+use crate::combat::status::{Status, StatusEffect, StatusEffects, tick_statuses, update_status_icons};
+use crate::combat::targeting::{AllyChosen, CombatTarget, despawn_target_cursor, move_target_cursor, select_target, spawn_target_cursor, stop_choosing_ally};
+use crate::combat::turns::{clear_turn_queue, next_turn, TurnQueue};
+use crate::consts::WHITE_ISH;
+use crate::core::assets::{PixelFont, spawn_tilesheet_sprite, Tilesheet};
+use crate::core::data::AddRonAsset;
+use crate::core::transition::create_fadeout;
+use crate::GameState;
+use crate::items::{Inventory, ItemDatabase, Items};
+use crate::leveling::apply_gains;
+use crate::party::Party;
+use crate::TILE_SIZE;
+use crate::ui::Disabled;
+
+/// Chance of running away when the party member is as fast as the fastest
+/// enemy, from 0 (never) to 1 (always)
+const RUN_CHANCE : f64 = 0.5;
+
+/// How much more likely running away is for each point of speed the party
+/// member has over the fastest enemy
+const RUN_CHANCE_PER_SPEED : f64 = 0.05;
+
+/// The lowest and highest chance running away can have
+const RUN_CHANCE_RANGE : (f64, f64) = (0.1, 0.95);
+
+/// How many turns ahead the turn order strip shows, counting the current
+/// one
+const TURN_ORDER_SHOWN : usize = 8;
+
+// Plugin
+// =========================================================================
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_state(CombatState::PlayerTurn)
+			.add_event::<FightEvent>()
+			.add_event::<CombatMessage>()
+			.add_event::<PopupEvent>()
+			.add_event::<AllyChosen>()
+			.add_ron_asset::<EnemyDatabase>(&["enemies.ron"])
+			.add_ron_asset::<EncounterTables>(&["encounters.ron"])
+			.add_ron_asset::<SkillDatabase>(&["skills.ron"])
+			.add_startup_system(load_enemies)
+			.add_startup_system(load_encounters)
+			.add_startup_system(load_skills)
+			.init_resource::<CombatTarget>()
+			.init_resource::<TurnQueue>()
+			.init_resource::<DamageRules>()
+			.init_resource::<CombatRng>()
+			.init_resource::<BattleLog>()
+			.init_resource::<DefeatSequence>()
+			.init_resource::<BattleResults>()
+			.insert_resource(AttackEffects {
+				timer: Timer::from_seconds(0.7, TimerMode::Repeating),
+				flash: 0.1,
+				shake: 0.5,
+				current_shake: 0.,
+				targets: Vec::new(),
+			})
+			.add_system_set(
+				SystemSet::on_enter(GameState::Combat)
+					.with_system(spawn_encounter)
+					.with_system(spawn_party)
+					.with_system(spawn_combat_ui)
+					.with_system(spawn_target_cursor)
+					.with_system(start_combat)
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::Combat)
+					.with_system(escape_combat)
+					.with_system(combat_camera)
+					.with_system(select_target.label("select_target"))
+					.with_system(move_target_cursor.after("select_target"))
+					.with_system(combat_input.after("select_target"))
+					.with_system(toggle_skill_menu)
+					.with_system(use_skill.after("select_target"))
+					.with_system(toggle_item_menu)
+					.with_system(use_item.after("select_target"))
+					.with_system(damage_calculation.label("damage_calculation"))
+					.with_system(update_combat_ui.after("damage_calculation").after("select_target"))
+					.with_system(update_turn_order_ui)
+					.with_system(attach_atb_gauges)
+					.with_system(fill_atb_gauges.before(next_turn))
+					.with_system(update_atb_bars)
+					.with_system(update_status_icons)
+					.with_system(record_combat_messages)
+					.with_system(spawn_popups)
+					.with_system(animate_popups)
+			)
+			.add_system_set(
+				SystemSet::on_update(CombatState::NextTurn)
+					.with_system(next_turn)
+			)
+			.add_system_set(
+				SystemSet::on_enter(CombatState::EnemyTurn(false))
+					.with_system(tick_statuses)
+			)
+			.add_system_set(
+				SystemSet::on_update(CombatState::EnemyTurn(false))
+					.with_system(process_enemy_turn)
+			)
+			.add_system_set(
+				SystemSet::on_update(CombatState::EnemyAttack)
+					.with_system(handle_attack_effects)
+			)
+			.add_system_set(
+				SystemSet::on_enter(CombatState::PlayerTurn)
+					.with_system(set_ui_disabled(false))
+					.with_system(tick_statuses)
+			)
+			.add_system_set(
+				SystemSet::on_exit(CombatState::PlayerTurn)
+					.with_system(set_ui_disabled(true))
+					.with_system(close_skill_menu)
+					.with_system(close_item_menu)
+					.with_system(stop_choosing_ally)
+			)
+			.add_system_set(
+				SystemSet::on_update(CombatState::PlayerAttack)
+					.with_system(handle_attack_effects)
+			)
+			.add_system_set(
+				SystemSet::on_enter(CombatState::Success)
+					.with_system(handle_success)
+			)
+			.add_system_set(
+				SystemSet::on_update(CombatState::Success)
+					.with_system(show_results)
+			)
+			.add_system_set(
+				SystemSet::on_enter(CombatState::Victory)
+					.with_system(spawn_results)
+			)
+			.add_system_set(
+				SystemSet::on_update(CombatState::Victory)
+					.with_system(update_results)
+			)
+			.add_system_set(
+				SystemSet::on_enter(CombatState::Defeat)
+					.with_system(start_defeat)
+			)
+			.add_system_set(
+				SystemSet::on_update(CombatState::Defeat)
+					.with_system(play_defeat_sequence)
+			)
+			.add_system_set(
+				SystemSet::on_exit(GameState::Combat)
+					.with_system(despawn_enemies)
+					.with_system(despawn_party)
+					.with_system(despawn_combat_ui)
+					.with_system(despawn_target_cursor)
+					.with_system(clear_turn_queue)
+					.with_system(clear_battle_log)
+					.with_system(despawn_popups)
+					.with_system(despawn_results)
+			)
+		;
+	}
+}
+
+// States
+// =========================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
+pub enum CombatState {
+	/// Waiting for the turn queue to say who acts next
+	NextTurn,
+	PlayerTurn,
+	PlayerAttack,
+	EnemyTurn (bool),
+	EnemyAttack,
+	/// Every enemy was defeated or ran away, and the rewards for those
+	/// defeated are being shared out
+	Success,
+	/// Showing what the party earned, until the player moves on
+	Victory,
+	/// The whole party was knocked out
+	Defeat,
+	/// The party ran away, or every enemy did before any were defeated, and
+	/// the battle is fading out
+	Fled,
+}
+
+// Data
+// =========================================================================
+
+/// Stats as written in data files, before anything is added on top
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaseStats {
+	pub health : isize,
+	pub attack : isize,
+	pub defence : isize,
+	pub speed : isize,
+	#[serde(default)]
+	pub mp : isize,
+}
+
+impl From<BaseStats> for CombatStats {
+	fn from (stats : BaseStats) -> Self {
+		CombatStats {
+			max_health: stats.health,
+			health: stats.health,
+			attack: stats.attack,
+			defence: stats.defence,
+			speed: stats.speed,
+			max_mp: stats.mp,
+			mp: stats.mp,
+		}
+	}
+}
+
+// Events
+// =========================================================================
+
+pub struct FightEvent {
+	/// Whoever is acting
+	attacker : Entity,
+	target : Entity,
+	/// Damage dealt, or health restored by heals
+	amount : isize,
+	effect : Effect,
+	/// Left on the target if they're still standing
+	status : Option<StatusEffect>,
+	next_state : CombatState,
+}
+
+// Resources
+// =========================================================================
+
+#[derive(Resource)]
+pub struct AttackEffects {
+	timer : Timer,
+	flash : f32,
+	shake : f32,
+	current_shake : f32,
+	/// Everyone to flash while the party's action plays out
+	targets : Vec<Entity>,
+}
+
+// Components
+// =========================================================================
+
+#[derive(Component)]
+pub struct Enemy;
+
+#[derive(Component, Debug, Clone)]
+pub struct CombatStats {
+	pub max_health : isize,
+	pub health : isize,
+	pub attack : isize,
+	pub defence : isize,
+	/// Decides the turn order, and how many actions a round the combatant
+	/// gets
+	pub speed : isize,
+	pub max_mp : isize,
+	/// Spent to use skills
+	pub mp : isize,
+}
+
+impl CombatStats {
+	/// How much health is left, from 0 (none) to 1 (full)
+	pub fn health_left (&self) -> f32 {
+		self.health as f32 / self.max_health.max(1) as f32
+	}
+}
+
+/// A member of the party fighting in the battle, by their place in the party
+#[derive(Component)]
+pub struct PartyCombatant (pub usize);
+
+#[derive(Component)]
+pub struct CombatUIRoot;
+
+#[derive(Component)]
+pub struct EnemyHealthText;
+
+#[derive(Component)]
+pub struct PlayerHealthText;
+
+/// A row with a label for each combatant, in the order they act
+#[derive(Component)]
+pub struct TurnOrderStrip;
+
+// Systems
+// =========================================================================
+
+// Combat Interaction
+// -------------------------------------------------------------------------
+
+fn start_combat (
+	mut state : ResMut<State<CombatState>>,
+) {
+	let _ = state.set(CombatState::NextTurn);
+}
+
+fn handle_attack_effects (
+	mut attack_fx : ResMut<AttackEffects>,
+	time : Res<Time>,
+	mut target_query : Query<(&mut Visibility, &CombatStats, Option<&Enemy>)>,
+	mut state : ResMut<State<CombatState>>,
+) {
+	attack_fx.timer.tick(time.delta());
+
+	match *state.current() {
+		CombatState::PlayerAttack => {
+			let finished = attack_fx.timer.just_finished();
+			let flash_on = attack_fx.timer.elapsed_secs() % attack_fx.flash > attack_fx.flash * 0.5;
+
+			for &id in &attack_fx.targets {
+				let Ok((mut visibility, stats, enemy)) = target_query.get_mut(id) else { continue };
+
+				// Defeated enemies stay hidden
+				visibility.is_visible = if finished {
+					enemy.is_none() || stats.health > 0
+				} else {
+					flash_on
+				};
+			}
+
+			if finished {
+				attack_fx.targets.clear();
+				state.set(CombatState::NextTurn).unwrap();
+			}
+		},
+		CombatState::EnemyAttack => {
+			if attack_fx.timer.just_finished() {
+				attack_fx.current_shake = 0.;
+				state.set(CombatState::NextTurn).unwrap();
+			} else {
+				attack_fx.current_shake = attack_fx.shake * f32::sin(
+					attack_fx.timer.percent() * 2. * PI
+				);
+			}
+		},
+		_ => {},
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+fn damage_calculation (
+	mut fight_event : EventReader<FightEvent>,
+	mut target_query: Query<(
+		&Name,
+		&mut CombatStats,
+		Option<&Enemy>,
+		Option<&Weaknesses>,
+		Option<&Resistances>,
+		Option<&mut StatusEffects>,
+	)>,
+	mut combat_state : ResMut<State<CombatState>>,
+	mut attack_fx : ResMut<AttackEffects>,
+	rules : Res<DamageRules>,
+	mut rng : ResMut<CombatRng>,
+	mut messages : EventWriter<CombatMessage>,
+	mut popups : EventWriter<PopupEvent>,
+) {
+	// Skills that hit several targets send an event for each
+	let mut next_state = None;
+
+	for event in fight_event.iter() {
+		let attacker = target_query
+			.get(event.attacker)
+			.map(|(_, stats, ..)| stats.clone())
+			.expect("Attacker missing combat stats!");
+
+		let (name, mut target_stats, enemy, weaknesses, resistances, mut statuses) = target_query
+			.get_mut(event.target)
+			.expect("Target missing combat stats!");
+
+		let standing = target_stats.health > 0;
+		let mut landed = true;
+
+		match event.effect {
+			Effect::Damage(element) => {
+				let hit = rules.0.roll(
+					&Attack {
+						power: event.amount,
+						element,
+						attacker: &attacker,
+					},
+					&Defender {
+						stats: &target_stats,
+						weak_to: weaknesses.map_or(&[], |weaknesses| &weaknesses.0[..]),
+						resists: resistances.map_or(&[], |resistances| &resistances.0[..]),
+						guarding: statuses.as_ref().is_some_and(|statuses| statuses.guarding()),
+					},
+					&mut rng.0,
+				);
+
+				match hit {
+					Hit::Miss => {
+						landed = false;
+						messages.send(CombatMessage(format!("{name} dodges!")));
+						popups.send(PopupEvent { target: event.target, popup: Popup::Miss });
+					}
+					Hit::Damage { amount, critical } => {
+						target_stats.health = max(target_stats.health - amount, 0);
+
+						if critical { messages.send(CombatMessage("Critical hit!".to_string())); }
+						messages.send(CombatMessage(format!("{name} takes {amount} damage.")));
+						popups.send(PopupEvent {
+							target: event.target,
+							popup: if critical { Popup::Critical(amount) } else { Popup::Damage(amount) },
+						});
+
+						let woke = statuses.as_mut().is_some_and(|statuses| statuses.wake());
+						if woke && target_stats.health > 0 {
+							messages.send(CombatMessage(format!("{name} wakes up.")));
+						}
+					}
+				}
+			}
+			Effect::Heal => {
+				let health = target_stats.health;
+				target_stats.health = min(
+					target_stats.health + event.amount,
+					target_stats.max_health,
+				);
+
+				let healed = target_stats.health - health;
+				messages.send(CombatMessage(format!("{name} recovers {healed} HP.")));
+				popups.send(PopupEvent { target: event.target, popup: Popup::Heal(healed) });
+			}
+			Effect::RestoreMp => {
+				let mp = target_stats.mp;
+				target_stats.mp = min(
+					target_stats.mp + event.amount,
+					target_stats.max_mp,
+				);
+
+				let restored = target_stats.mp - mp;
+				messages.send(CombatMessage(format!("{name} recovers {restored} MP.")));
+				popups.send(PopupEvent { target: event.target, popup: Popup::Mp(restored) });
+			}
+			Effect::Status => {},
+		}
+
+		if let Some(statuses) = &mut statuses {
+			if target_stats.health == 0 {
+				statuses.clear(&mut target_stats);
+			} else if let Some(effect) = event.status.filter(|_| landed) {
+				statuses.add(effect, &mut target_stats);
+				messages.send(CombatMessage(effect.status.message(name)));
+			}
+		}
+
+		if standing && target_stats.health == 0 {
+			messages.send(CombatMessage(knocked_out_message(name, enemy.is_some())));
+		}
+
+		if event.next_state == CombatState::PlayerAttack {
+			attack_fx.targets.push(event.target);
+		}
+
+		next_state = Some(event.next_state);
+	}
+
+	let Some(next_state) = next_state else { return };
+
+	if let Some(outcome) = battle_outcome(target_query.iter().map(|(_, stats, enemy, ..)| (stats.health, enemy.is_some()))) {
+		combat_state.set(outcome).expect("Failed to set exit state");
+	} else {
+		combat_state.set(next_state).expect("Failed to set player turn state");
+	}
+}
+
+/// Tries to run from the battle, more likely the faster whoever's turn it
+/// is compared to the fastest enemy. Failing costs the turn.
+#[allow(clippy::too_many_arguments)]
+fn escape_combat (
+	mut commands : Commands,
+	query : Query<(&Interaction, &Name), (Changed<Interaction>, With<Button>)>,
+	party_query : Query<&CombatStats, With<PartyCombatant>>,
+	enemy_query : Query<&CombatStats, With<Enemy>>,
+	encounter : Option<Res<Encounter>>,
+	turns : Res<TurnQueue>,
+	mut rng : ResMut<CombatRng>,
+	mut messages : EventWriter<CombatMessage>,
+	mut state : ResMut<State<CombatState>>,
+) {
+	if state.current() != &CombatState::PlayerTurn { return; }
+
+	let clicked = query
+		.iter()
+		.any(|(interaction, name)| *interaction == Interaction::Clicked && name.as_str() == "run");
+	if !clicked { return; }
+
+	if encounter.is_some_and(|encounter| encounter.inescapable) {
+		messages.send(CombatMessage("There's no running from this fight!".to_string()));
+		return;
+	}
+
+	let Some(member) = turns.current.and_then(|id| party_query.get(id).ok()) else { return };
+	let fastest = enemy_query
+		.iter()
+		.filter(|stats| stats.health > 0)
+		.map(|stats| stats.speed)
+		.max()
+		.unwrap_or(0);
+
+	let (min_chance, max_chance) = RUN_CHANCE_RANGE;
+	let chance = (RUN_CHANCE + (member.speed - fastest) as f64 * RUN_CHANCE_PER_SPEED)
+		.clamp(min_chance, max_chance);
+
+	if rng.0.gen_bool(chance) {
+		messages.send(CombatMessage("You got away!".to_string()));
+		create_fadeout(
+			&mut commands,
+			None,
+		);
+
+		state.set(CombatState::Fled).expect("Failed to set fled state");
+	} else {
+		messages.send(CombatMessage("Couldn't get away!".to_string()));
+		state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+	}
+}
+
+fn combat_input (
+	query : Query<(&Interaction, &Name), (Changed<Interaction>, With<Button>)>,
+	mut fight_event : EventWriter<FightEvent>,
+	mut messages : EventWriter<CombatMessage>,
+	mut party_query : Query<(&Name, &mut CombatStats, &mut StatusEffects), With<PartyCombatant>>,
+	turns : Res<TurnQueue>,
+	target : Res<CombatTarget>,
+	mut state : ResMut<State<CombatState>>,
+) {
+	if state.current() != &CombatState::PlayerTurn { return; }
+
+	let Some(id) = turns.current else { return };
+	let Ok((member_name, mut member, mut statuses)) = party_query.get_mut(id) else { return };
+
+	for (interaction, name) in &query {
+		if *interaction != Interaction::Clicked { continue; }
+
+		match name.as_str() {
+			"fight" => {
+				let Some(target) = target.enemy else { continue };
+
+				messages.send(CombatMessage(format!("{member_name} attacks!")));
+				fight_event.send(FightEvent {
+					attacker: id,
+					target,
+					amount: member.attack,
+					effect: Effect::Damage(Element::Physical),
+					status: None,
+					next_state: CombatState::PlayerAttack,
+				});
+			}
+			"defend" => {
+				// Lasts until the member's next turn starts
+				let guard = StatusEffect { status: Status::Guard, turns: 1 };
+				statuses.add(guard, &mut member);
+
+				messages.send(CombatMessage(guard.status.message(member_name)));
+				state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+			}
+			_ => {},
+		}
+	}
+}
+
+/// Shares out the XP, gold and loot from the defeated enemies. Members
+/// still standing earn the XP, levelling up if it's enough.
+#[allow(clippy::too_many_arguments)]
+fn handle_success (
+	enemy_query : Query<&EnemyRewards, With<Enemy>>,
+	mut party_query : Query<(&PartyCombatant, &mut CombatStats), Without<Enemy>>,
+	mut party : ResMut<Party>,
+	mut results : ResMut<BattleResults>,
+	mut inventory : ResMut<Inventory>,
+	items : Res<Items>,
+	item_databases : Res<Assets<ItemDatabase>>,
+	mut rng : ResMut<CombatRng>,
+	mut messages : EventWriter<CombatMessage>,
+) {
+	let item_database = item_databases.get(&items.0);
+
+	let xp : usize = enemy_query.iter().map(|rewards| rewards.xp).sum();
+	let gold : usize = enemy_query.iter().map(|rewards| rewards.gold).sum();
+	inventory.gold += gold;
+	messages.send(CombatMessage(format!("You gained {xp} XP and {gold} gold.")));
+
+	*results = BattleResults { xp, gold, ..default() };
+
+	for (combatant, mut stats) in &mut party_query {
+		if stats.health == 0 { continue; }
+		let Some(member) = party.members.get_mut(combatant.0) else { continue };
+
+		let xp_before = member.xp;
+		let level_up = member.gain_xp(xp);
+
+		// Gains go on the battle stats too, as they're what's kept afterwards
+		if let Some(level_up) = &level_up {
+			apply_gains(&mut stats, &level_up.gains);
+		}
+
+		results.members.push(MemberResults {
+			name: member.name.clone(),
+			curve: member.growth.curve,
+			xp_before,
+			xp_after: member.xp,
+			level_up,
+		});
+	}
+
+	for rewards in &enemy_query {
+		for drop in &rewards.loot {
+			if rng.0.gen_bool(drop.chance) {
+				let name = item_database.map_or(drop.item.as_str(), |database| database.name(&drop.item));
+				messages.send(CombatMessage(format!("You found a {name}!")));
+				inventory.add(&drop.item, 1);
+				results.items.push(name.to_string());
+			}
+		}
+	}
+}
+
+// Combat UI
+// -------------------------------------------------------------------------
+
+fn set_ui_disabled (is_disabled : bool) -> impl Fn(Commands, Query<Entity, With<Button>>) {
+	move |
+		mut commands : Commands,
+		query : Query<Entity, With<Button>>,
+	| {
+		for btn in &query {
+			let mut e = commands.entity(btn);
+			if is_disabled { e.insert(Disabled); }
+			else { e.remove::<Disabled>(); }
+		}
+	}
+}
+
+fn spawn_combat_ui (
+	mut commands : Commands,
+	pixel_font : Res<PixelFont>,
+) {
+	commands
+		.spawn((
+			CombatUIRoot,
+			NodeBundle {
+				style: Style {
+					size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+					position_type: PositionType::Absolute,
+					flex_direction: FlexDirection::Column,
+					padding: UiRect::all(Val::Px(30.)),
+					justify_content: JustifyContent::SpaceBetween,
+					..default()
+				},
+				..default()
+			},
+		))
+		.with_children(|parent| {
+			parent.spawn((
+				TextBundle {
+					text: Text::from_section(
+						"",
+						TextStyle {
+							font: pixel_font.0.clone(),
+							font_size: 25.,
+							color: WHITE_ISH,
+						},
+					),
+					style: Style {
+						position_type: PositionType::Absolute,
+						position: UiRect {
+							top: Val::Px(30.),
+							right: Val::Px(30.),
+							..default()
+						},
+						..default()
+					},
+					..default()
+				},
+				BattleLogText,
+			));
+
+			parent.spawn(NodeBundle {
+				style: Style {
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::FlexStart,
+					..default()
+				},
+				..default()
+			})
+				.with_children(|parent| {
+					parent.spawn((
+						TextBundle::from_section(
+							"Enemy HP: X",
+							TextStyle {
+								font: pixel_font.0.clone(),
+								font_size: 40.,
+								color: Color::WHITE,
+							},
+						),
+						EnemyHealthText,
+					));
+
+					parent.spawn((
+						TextBundle::from_section(
+							"Player HP: X",
+							TextStyle {
+								font: pixel_font.0.clone(),
+								font_size: 40.,
+								color: Color::WHITE,
+							},
+						),
+						PlayerHealthText,
+					));
+
+					parent.spawn((
+						NodeBundle {
+							style: Style {
+								align_items: AlignItems::Center,
+								margin: UiRect::top(Val::Px(10.)),
+								..default()
+							},
+							..default()
+						},
+						TurnOrderStrip,
+					));
+				});
+
+			parent.spawn(NodeBundle {
+				style: Style {
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::FlexEnd,
+					align_self: AlignSelf::FlexEnd,
+					..default()
+				},
+				..default()
+			}).with_children(|parent| {
+				let menu_node = || NodeBundle {
+					style: Style {
+						display: Display::None,
+						flex_direction: FlexDirection::Column,
+						align_items: AlignItems::FlexEnd,
+						margin: UiRect::bottom(Val::Px(10.)),
+						..default()
+					},
+					..default()
+				};
+
+				parent.spawn((SkillMenu, menu_node()));
+				parent.spawn((ItemMenu, menu_node()));
+
+				parent.spawn(NodeBundle {
+					style: Style {
+						align_items: AlignItems::FlexEnd,
+						justify_content: JustifyContent::FlexEnd,
+						..default()
+					},
+					..default()
+				}).with_children(|parent| {
+					let buttons = [
+						("fight", "Fight", "D5543B"),
+						("skills", "Skills", "5B6EE1"),
+						("item", "Item", "6ED57E"),
+						("defend", "Defend", "8B9BB4"),
+						("run", "Run", "EAB644"),
+					];
+
+					for (i, (id, label, color)) in buttons.into_iter().enumerate() {
+						let last = i == buttons.len() - 1;
+
+						parent.spawn((
+							ButtonBundle {
+								style: Style {
+									size: Size::new(Val::Px(150.), Val::Px(65.)),
+									justify_content: JustifyContent::Center,
+									align_items: AlignItems::Center,
+									margin: UiRect::right(Val::Px(if last { 0. } else { 10. })),
+									..default()
+								},
+								background_color: Color::hex(color).unwrap().into(),
+								..default()
+							},
+							Name::new(id),
+						)).with_children(|parent| {
+							parent.spawn(TextBundle::from_section(
+								label,
+								TextStyle {
+									font: pixel_font.0.clone(),
+									font_size: 40.,
+									color: Color::WHITE,
+								},
+							));
+						});
+					}
+				});
+			});
+		});
+}
+
+fn update_combat_ui (
+	enemy_stats_query : Query<(Entity, &Name, &Transform, &CombatStats), With<Enemy>>,
+	party_stats_query : Query<(Entity, &Name, &PartyCombatant, &CombatStats)>,
+	target : Res<CombatTarget>,
+	turns : Res<TurnQueue>,
+	mut enemy_health_text_query : Query<&mut Text, (With<EnemyHealthText>, Without<PlayerHealthText>)>,
+	mut player_health_text_query : Query<&mut Text, (With<PlayerHealthText>, Without<EnemyHealthText>)>,
+) {
+	if let Ok(mut enemy_health_text) = enemy_health_text_query.get_single_mut() {
+		let mut enemies : Vec<_> = enemy_stats_query.iter().collect();
+		enemies.sort_by(|a, b| a.2.translation.x.total_cmp(&b.2.translation.x));
+
+		enemy_health_text.sections[0].value = enemies
+			.iter()
+			.map(|(id, name, _, stats)| format!(
+				"{} {name} HP: {}",
+				if target.choosing.is_none() && target.enemy == Some(*id) { ">" } else { " " },
+				stats.health,
+			))
+			.collect::<Vec<_>>()
+			.join("\n");
+	}
+
+	if let Ok(mut player_health_text) = player_health_text_query.get_single_mut() {
+		let mut members : Vec<_> = party_stats_query.iter().collect();
+		members.sort_by_key(|(_, _, combatant, _)| combatant.0);
+
+		player_health_text.sections[0].value = members
+			.iter()
+			.map(|(id, name, _, stats)| format!(
+				"{} {name} HP: {} MP: {}",
+				if turns.current == Some(*id) { ">" } else { " " },
+				stats.health,
+				stats.mp,
+			))
+			.collect::<Vec<_>>()
+			.join("\n");
+	}
+}
+
+/// Lines up a label for each combatant in the order they act, starting
+/// with whoever's turn it is. Party members are blue and enemies red.
+fn update_turn_order_ui (
+	mut commands : Commands,
+	turns : Res<TurnQueue>,
+	pixel_font : Res<PixelFont>,
+	combatant_query : Query<(&Name, Option<&Enemy>)>,
+	strip_query : Query<(Entity, ChangeTrackers<TurnOrderStrip>)>,
+	mut shown : Local<Vec<Entity>>,
+) {
+	let Ok((strip, tracker)) = strip_query.get_single() else { return };
+
+	let order : Vec<Entity> = turns.current
+		.iter()
+		.chain(turns.upcoming.iter())
+		.copied()
+		.filter(|&id| combatant_query.contains(id))
+		.take(TURN_ORDER_SHOWN)
+		.collect();
+
+	if order == *shown && !tracker.is_added() { return; }
+
+	commands.entity(strip).despawn_descendants();
+	commands.entity(strip).with_children(|parent| {
+		for (i, &id) in order.iter().enumerate() {
+			let Ok((name, enemy)) = combatant_query.get(id) else { continue };
+			let acting = i == 0 && turns.current == Some(id);
+
+			let mut color = Color::hex(if enemy.is_some() { "D5543B" } else { "5B6EE1" }).unwrap();
+			if !acting { color.set_a(0.6); }
+
+			parent.spawn(NodeBundle {
+				style: Style {
+					padding: UiRect::all(Val::Px(6.)),
+					margin: UiRect::right(Val::Px(6.)),
+					..default()
+				},
+				background_color: color.into(),
+				..default()
+			}).with_children(|parent| {
+				parent.spawn(TextBundle::from_section(
+					name.as_str(),
+					TextStyle {
+						font: pixel_font.0.clone(),
+						font_size: if acting { 30. } else { 24. },
+						color: Color::WHITE,
+					},
+				));
+			});
+		}
+	});
+
+	*shown = order;
+}
+
+fn despawn_combat_ui (
+	mut commands : Commands,
+	query : Query<Entity, With<CombatUIRoot>>,
+) {
+	commands.entity(query.single()).despawn_recursive();
+}
+
+// Enemy
+// -------------------------------------------------------------------------
+
+fn spawn_encounter (
+	mut commands : Commands,
+	tilesheet : Res<Tilesheet>,
+	encounter : Option<Res<Encounter>>,
+	enemies : Res<Enemies>,
+	databases : Res<Assets<EnemyDatabase>>,
+) {
+	let database = databases.get(&enemies.0);
+
+	let rolled : Vec<_> = encounter.as_ref().zip(database).map(|(encounter, database)| {
+		encounter.enemies
+			.iter()
+			.filter_map(|enemy| match database.0.get(&enemy.id) {
+				Some(def) => Some((def, enemy.level)),
+				None => {
+					error!("Encounter has an unknown enemy '{}'", enemy.id);
+					None
+				}
+			})
+			.collect()
+	}).unwrap_or_default();
+
+	if rolled.is_empty() {
+		error!("The encounter has no enemies to fight");
+		create_fadeout(&mut commands, None);
+		return;
+	}
+
+	// Line the group up side by side, centred on the screen
+	let middle = (rolled.len() - 1) as f32 * 0.5;
+	for (i, (def, level)) in rolled.into_iter().enumerate() {
+		let x = (i as f32 - middle) * TILE_SIZE * 1.5;
+		spawn_enemy(&mut commands, &tilesheet, def, level, Vec3::new(x, 0., 0.));
+	}
+}
+
+fn despawn_enemies (
+	mut commands : Commands,
+	query : Query<Entity, With<Enemy>>,
+) {
+	commands.remove_resource::<Encounter>();
+
+	for id in &query {
+		commands
+			.entity(id)
+			.despawn_recursive();
+	}
+}
+
+// Party
+// -------------------------------------------------------------------------
+
+fn spawn_party (
+	mut commands : Commands,
+	tilesheet : Res<Tilesheet>,
+	party : Res<Party>,
+) {
+	// Line the party up below the enemies
+	let middle = party.members.len().saturating_sub(1) as f32 * 0.5;
+
+	for (i, member) in party.members.iter().enumerate() {
+		let x = (i as f32 - middle) * TILE_SIZE * 1.5;
+		let id = spawn_tilesheet_sprite(
+			&mut commands,
+			&tilesheet,
+			member.sprite,
+			Vec3::new(x, -TILE_SIZE * 2.5, 0.),
+			None,
+		);
+
+		// Statuses that lingered from the last battle carry on
+		let mut stats = member.combat_stats();
+		let mut statuses = StatusEffects::default();
+		for effect in &member.statuses {
+			statuses.add(*effect, &mut stats);
+		}
+
+		commands.entity(id).insert((
+			Name::new(member.name.clone()),
+			PartyCombatant(i),
+			stats,
+			statuses,
+			KnownSkills(member.skills.clone()),
+		));
+	}
+}
+
+fn despawn_party (
+	mut commands : Commands,
+	query : Query<(Entity, &PartyCombatant, &CombatStats, &StatusEffects)>,
+	mut party : ResMut<Party>,
+) {
+	for (id, combatant, stats, statuses) in &query {
+		// Members knocked out in battle come round with 1 HP
+		if let Some(member) = party.members.get_mut(combatant.0) {
+			member.stats.health = stats.health.max(1);
+			member.stats.mp = stats.mp;
+			member.statuses = statuses.0
+				.iter()
+				.filter(|effect| effect.status.lingers())
+				.copied()
+				.collect();
+		}
+
+		commands.entity(id).despawn_recursive();
+	}
+}
+
+// Scene
+// -------------------------------------------------------------------------
+
+fn combat_camera (
+	mut query : Query<&mut Transform, With<Camera>>,
+	mut ui_query : Query<&mut Style, With<CombatUIRoot>>,
+	attack_fx : Res<AttackEffects>,
+) {
+	let mut camera = query.single_mut();
+	camera.translation.x = attack_fx.current_shake;
+	camera.translation.y = 0.;
+
+	ui_query.single_mut().position = UiRect {
+		left: Val::Percent(-attack_fx.current_shake),
+		right: Val::Percent(attack_fx.current_shake),
+		..default()
+	};
+}
+
+// Helpers
+// =========================================================================
+
+/// How the battle ended, from the health of each combatant and whether
+/// they're an enemy, or `None` if both sides still have someone standing
+fn battle_outcome (combatants : impl Iterator<Item = (isize, bool)>) -> Option<CombatState> {
+	let (mut party_standing, mut enemies_standing) = (false, false);
+
+	for (health, enemy) in combatants {
+		if health == 0 { continue; }
+		if enemy { enemies_standing = true; } else { party_standing = true; }
+	}
+
+	if !party_standing {
+		Some(CombatState::Defeat)
+	} else if !enemies_standing {
+		Some(CombatState::Success)
+	} else {
+		None
+	}
+}
+
+/// What the battle log says when a combatant goes down
+fn knocked_out_message (name : &str, enemy : bool) -> String {
+	if enemy {
+		format!("{name} is defeated!")
+	} else {
+		format!("{name} is knocked out!")
+	}
+}
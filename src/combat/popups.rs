@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use crate::consts::WHITE_ISH;
+use crate::core::assets::PixelFont;
+use crate::TILE_SIZE;
+
+/// How long a popup takes to rise and fade away, in seconds
+const POPUP_LIFETIME : f32 = 0.9;
+
+/// How far a popup rises before it's gone, in tiles
+const POPUP_RISE : f32 = 0.6;
+
+/// Font size popups are drawn at, before being scaled down to the world
+const POPUP_FONT_SIZE : f32 = 60.;
+
+// Events
+// =========================================================================
+
+/// Something that happened to a combatant, shown over their sprite
+pub struct PopupEvent {
+	pub target : Entity,
+	pub popup : Popup,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Popup {
+	Damage (isize),
+	Critical (isize),
+	Heal (isize),
+	/// MP restored
+	Mp (isize),
+	Miss,
+}
+
+impl Popup {
+	fn text (&self) -> String {
+		match self {
+			Popup::Damage(amount) => amount.to_string(),
+			Popup::Critical(amount) => format!("{amount}!"),
+			Popup::Heal(amount) => format!("+{amount}"),
+			Popup::Mp(amount) => format!("+{amount} MP"),
+			Popup::Miss => "Miss".to_string(),
+		}
+	}
+
+	fn color (&self) -> Color {
+		match self {
+			Popup::Damage(_) => Color::WHITE,
+			Popup::Critical(_) => Color::hex("EAB644").unwrap(),
+			Popup::Heal(_) => Color::hex("6ED57E").unwrap(),
+			Popup::Mp(_) => Color::hex("5B6EE1").unwrap(),
+			Popup::Miss => WHITE_ISH,
+		}
+	}
+}
+
+// Components
+// =========================================================================
+
+/// A number floating up from a combatant
+#[derive(Component)]
+pub struct DamagePopup {
+	timer : Timer,
+	start : Vec3,
+}
+
+// Systems
+// =========================================================================
+
+pub fn spawn_popups (
+	mut commands : Commands,
+	mut events : EventReader<PopupEvent>,
+	target_query : Query<&GlobalTransform>,
+	pixel_font : Res<PixelFont>,
+) {
+	for event in events.iter() {
+		let Ok(target) = target_query.get(event.target) else { continue };
+		let start = target.translation() + Vec3::new(0., TILE_SIZE * 0.5, 50.);
+
+		commands.spawn((
+			DamagePopup {
+				timer: Timer::from_seconds(POPUP_LIFETIME, TimerMode::Once),
+				start,
+			},
+			Text2dBundle {
+				text: Text::from_section(
+					event.popup.text(),
+					TextStyle {
+						font: pixel_font.0.clone(),
+						font_size: POPUP_FONT_SIZE,
+						color: event.popup.color(),
+					},
+				).with_alignment(TextAlignment::CENTER),
+				transform: Transform::from_translation(start)
+					.with_scale(Vec3::splat(TILE_SIZE * 0.6 / POPUP_FONT_SIZE)),
+				..default()
+			},
+		));
+	}
+}
+
+/// Floats popups upwards, fading them out as they go
+pub fn animate_popups (
+	mut commands : Commands,
+	time : Res<Time>,
+	mut query : Query<(Entity, &mut DamagePopup, &mut Transform, &mut Text)>,
+) {
+	for (id, mut popup, mut transform, mut text) in &mut query {
+		popup.timer.tick(time.delta());
+
+		if popup.timer.finished() {
+			commands.entity(id).despawn_recursive();
+			continue;
+		}
+
+		let t = popup.timer.percent();
+		transform.translation = popup.start + Vec3::Y * POPUP_RISE * TILE_SIZE * t;
+
+		// Stay solid for the first half, then fade
+		for section in &mut text.sections {
+			section.style.color.set_a(1. - ((t - 0.5) * 2.).max(0.));
+		}
+	}
+}
+
+pub fn despawn_popups (
+	mut commands : Commands,
+	query : Query<Entity, With<DamagePopup>>,
+) {
+	for id in &query {
+		commands.entity(id).despawn_recursive();
+	}
+}
@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use bevy::prelude::*;
+use crate::combat::CombatState;
+use crate::core::assets::PixelFont;
+use crate::core::transition::create_fadeout;
+use crate::leveling::{LevelUp, LevelUpEvent, MAX_LEVEL, XpCurve};
+
+/// How long the XP bars take to fill up, in seconds
+const XP_FILL_DURATION : f32 = 1.5;
+
+/// Keys that skip the XP bars, then leave the battle
+const CONFIRM_KEYS : [KeyCode; 3] = [KeyCode::Space, KeyCode::Return, KeyCode::E];
+
+const XP_BAR_WIDTH : f32 = 240.;
+
+// Resources
+// =========================================================================
+
+/// What the party earned from a won battle
+#[derive(Resource, Default, Debug)]
+pub struct BattleResults {
+	pub xp : usize,
+	pub gold : usize,
+	/// Display names of the items dropped, once per drop
+	pub items : Vec<String>,
+	/// Everyone who was still standing to earn the XP
+	pub members : Vec<MemberResults>,
+}
+
+/// How a party member's XP changed over the battle
+#[derive(Debug, Clone)]
+pub struct MemberResults {
+	pub name : String,
+	pub curve : XpCurve,
+	/// Total XP before the battle
+	pub xp_before : usize,
+	/// Total XP after the battle
+	pub xp_after : usize,
+	pub level_up : Option<LevelUp>,
+}
+
+impl MemberResults {
+	/// The member's level, and how far through it they are from 0 to 1, at
+	/// `t` of the way through the XP bar filling up
+	fn progress (&self, t : f32) -> (u32, f32) {
+		let gained = self.xp_after.saturating_sub(self.xp_before) as f32 * t;
+		let xp = self.xp_before + gained.round() as usize;
+		let level = self.curve.level_at(xp);
+		if level >= MAX_LEVEL { return (level, 1.); }
+
+		let start = self.curve.total_for(level);
+		let end = self.curve.total_for(level + 1);
+		(level, (xp - start) as f32 / end.saturating_sub(start).max(1) as f32)
+	}
+}
+
+// Components
+// =========================================================================
+
+#[derive(Component)]
+pub struct ResultsUIRoot {
+	fill : Timer,
+	/// Set once the player has moved on, while the battle fades out
+	closing : bool,
+}
+
+/// The filled part of a member's XP bar, by index into
+/// [`BattleResults::members`]
+#[derive(Component)]
+pub struct XpBar (usize);
+
+#[derive(Component)]
+pub struct LevelText (usize);
+
+#[derive(Component)]
+pub struct LevelUpText (usize);
+
+// Systems
+// =========================================================================
+
+/// Moves on to the results once the rewards have been shared out
+pub fn show_results (
+	mut state : ResMut<State<CombatState>>,
+) {
+	let _ = state.set(CombatState::Victory);
+}
+
+pub fn spawn_results (
+	mut commands : Commands,
+	pixel_font : Res<PixelFont>,
+	results : Res<BattleResults>,
+) {
+	let mut bg = Color::hex("432E3B").unwrap();
+	bg.set_a(0.9);
+
+	let text_style = |font_size : f32, color : &str| TextStyle {
+		font: pixel_font.0.clone(),
+		font_size,
+		color: Color::hex(color).unwrap(),
+	};
+
+	commands.spawn((
+		ResultsUIRoot {
+			fill: Timer::from_seconds(XP_FILL_DURATION, TimerMode::Once),
+			closing: false,
+		},
+		NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+				..default()
+			},
+			z_index: ZIndex::Global(10),
+			..default()
+		},
+	)).with_children(|parent| {
+		parent.spawn(NodeBundle {
+			style: Style {
+				margin: UiRect::all(Val::Auto),
+				padding: UiRect::all(Val::Px(30.)),
+				flex_direction: FlexDirection::Column,
+				align_items: AlignItems::Center,
+				..default()
+			},
+			background_color: BackgroundColor::from(bg),
+			..default()
+		}).with_children(|parent| {
+			parent.spawn(TextBundle::from_section(
+				"Victory!",
+				text_style(60., "EAB644"),
+			).with_style(Style {
+				margin: UiRect::bottom(Val::Px(20.)),
+				..default()
+			}));
+
+			parent.spawn(TextBundle::from_section(
+				format!("XP +{}   Gold +{}", results.xp, results.gold),
+				text_style(40., "FFFFFF"),
+			));
+
+			// Drops of the same item are counted up, like "Herb x2"
+			if !results.items.is_empty() {
+				let mut found = BTreeMap::<&str, usize>::new();
+				for name in &results.items { *found.entry(name).or_default() += 1; }
+
+				let found = found
+					.iter()
+					.map(|(name, count)| match count {
+						1 => name.to_string(),
+						_ => format!("{name} x{count}"),
+					})
+					.collect::<Vec<_>>()
+					.join(", ");
+
+				parent.spawn(TextBundle::from_section(
+					format!("Found {found}"),
+					text_style(30., "EAB644"),
+				).with_style(Style {
+					margin: UiRect::top(Val::Px(10.)),
+					..default()
+				}));
+			}
+
+			for (index, member) in results.members.iter().enumerate() {
+				let (level, progress) = member.progress(0.);
+
+				parent.spawn(NodeBundle {
+					style: Style {
+						margin: UiRect::top(Val::Px(20.)),
+						align_items: AlignItems::Center,
+						..default()
+					},
+					..default()
+				}).with_children(|parent| {
+					parent.spawn((
+						TextBundle::from_section(
+							format!("{} Lv {level}", member.name),
+							text_style(30., "FFFFFF"),
+						).with_style(Style {
+							size: Size::new(Val::Px(200.), Val::Auto),
+							..default()
+						}),
+						LevelText(index),
+					));
+
+					parent.spawn(NodeBundle {
+						style: Style {
+							size: Size::new(Val::Px(XP_BAR_WIDTH), Val::Px(14.)),
+							..default()
+						},
+						background_color: Color::hex("6B6B6B").unwrap().into(),
+						..default()
+					}).with_children(|parent| {
+						parent.spawn((
+							NodeBundle {
+								style: Style {
+									size: Size::new(Val::Percent(progress * 100.), Val::Percent(100.)),
+									..default()
+								},
+								background_color: Color::hex("5B6EE1").unwrap().into(),
+								..default()
+							},
+							XpBar(index),
+						));
+					});
+				});
+
+				parent.spawn((
+					TextBundle::from_section("", text_style(25., "6ED57E"))
+						.with_text_alignment(TextAlignment::TOP_CENTER),
+					LevelUpText(index),
+				));
+			}
+
+			parent.spawn(TextBundle::from_section(
+				"Press SPACE to continue",
+				text_style(24., "8B9BB4"),
+			).with_style(Style {
+				margin: UiRect::top(Val::Px(30.)),
+				..default()
+			}));
+		});
+	});
+}
+
+/// Fills up the XP bars, showing level ups with a fanfare as each one is
+/// reached. Confirming skips to the end, then fades out of the battle.
+#[allow(clippy::too_many_arguments)]
+pub fn update_results (
+	mut commands : Commands,
+	time : Res<Time>,
+	keyboard : Res<Input<KeyCode>>,
+	results : Res<BattleResults>,
+	mut root_query : Query<&mut ResultsUIRoot>,
+	mut bar_query : Query<(&XpBar, &mut Style)>,
+	mut level_query : Query<(&LevelText, &mut Text), Without<LevelUpText>>,
+	mut level_up_query : Query<(&LevelUpText, &mut Text), Without<LevelText>>,
+	mut level_up_events : EventWriter<LevelUpEvent>,
+) {
+	let Ok(mut root) = root_query.get_single_mut() else { return };
+	if root.closing { return; }
+
+	let confirmed = keyboard.any_just_pressed(CONFIRM_KEYS);
+
+	if root.fill.finished() {
+		if confirmed {
+			root.closing = true;
+			create_fadeout(
+				&mut commands,
+				None,
+			);
+		}
+		return;
+	}
+
+	let delta = if confirmed { root.fill.remaining() } else { time.delta() };
+	root.fill.tick(delta);
+	let t = root.fill.percent();
+
+	for (bar, mut style) in &mut bar_query {
+		let Some(member) = results.members.get(bar.0) else { continue };
+		let (_, progress) = member.progress(t);
+		style.size.width = Val::Percent(progress * 100.);
+	}
+
+	for (level_text, mut text) in &mut level_query {
+		let Some(member) = results.members.get(level_text.0) else { continue };
+		let (level, _) = member.progress(t);
+		text.sections[0].value = format!("{} Lv {level}", member.name);
+	}
+
+	let mut fanfare = false;
+
+	for (level_up_text, mut text) in &mut level_up_query {
+		let Some(member) = results.members.get(level_up_text.0) else { continue };
+		let Some(level_up) = &member.level_up else { continue };
+		if !text.sections[0].value.is_empty() || member.progress(t).0 < level_up.level { continue; }
+
+		text.sections[0].value = format!(
+			"{} reached level {}!\n{}",
+			level_up.name,
+			level_up.level,
+			level_up.describe_gains(),
+		);
+		fanfare = true;
+	}
+
+	if fanfare { level_up_events.send(LevelUpEvent); }
+}
+
+pub fn despawn_results (
+	mut commands : Commands,
+	query : Query<Entity, With<ResultsUIRoot>>,
+) {
+	commands.insert_resource(BattleResults::default());
+
+	for id in &query {
+		commands.entity(id).despawn_recursive();
+	}
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use crate::combat::{CombatState, CombatStats, Enemy, FightEvent, PartyCombatant};
+use crate::combat::log::CombatMessage;
+use crate::combat::status::StatusEffect;
+use crate::combat::targeting::{AllyAction, AllyChosen, CombatTarget};
+use crate::combat::turns::TurnQueue;
+use crate::core::assets::PixelFont;
+use crate::ui::Disabled;
+
+// Assets
+// =========================================================================
+
+/// Every skill combatants can learn, keyed by id. Loaded from
+/// `*.skills.ron` files.
+///
+/// ```ron
+/// {
+///     "fire": (
+///         name: "Fire",
+///         cost: 3,
+///         power: 6,
+///         effect: Damage(Fire),
+///         target: Single,
+///     ),
+///     "sleep": (
+///         name: "Sleep",
+///         cost: 3,
+///         power: 0,
+///         effect: Status,
+///         target: Single,
+///         status: Some((status: Sleep, turns: 3)),
+///     ),
+/// }
+/// ```
+#[derive(TypeUuid, Deserialize, Debug, Default)]
+#[uuid = "c4a1f6d2-7e38-4b95-a0c7-1d2e5f8b9a63"]
+#[serde(transparent)]
+pub struct SkillDatabase (pub HashMap<String, SkillDef>);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SkillDef {
+	pub name : String,
+	/// MP spent to use the skill
+	pub cost : isize,
+	/// Damage dealt or health restored, before defence and variance
+	pub power : isize,
+	pub effect : Effect,
+	pub target : SkillTarget,
+	/// Left on each target that's still standing afterwards
+	#[serde(default)]
+	pub status : Option<StatusEffect>,
+}
+
+/// What an action does to its target
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+	Damage (Element),
+	Heal,
+	/// Restores MP instead of health
+	RestoreMp,
+	/// Nothing besides the action's status
+	Status,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Element {
+	#[default]
+	Physical,
+	Fire,
+	Ice,
+	Thunder,
+}
+
+/// Who a skill is used on
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkillTarget {
+	/// The targeted enemy
+	Single,
+	/// Every enemy still standing
+	All,
+	/// Whoever uses the skill
+	Caster,
+	/// The party member the player chooses
+	Ally,
+	/// Every party member still standing
+	Party,
+}
+
+// Components
+// =========================================================================
+
+/// The ids of the skills a combatant can use
+#[derive(Component, Debug, Clone, Default)]
+pub struct KnownSkills (pub Vec<String>);
+
+/// Elements that deal double damage to a combatant
+#[derive(Component, Debug, Clone, Default)]
+pub struct Weaknesses (pub Vec<Element>);
+
+/// Elements that deal half damage to a combatant
+#[derive(Component, Debug, Clone, Default)]
+pub struct Resistances (pub Vec<Element>);
+
+/// The list of skills opened from the combat menu
+#[derive(Component)]
+pub struct SkillMenu;
+
+/// A button in the skill menu, by skill id
+#[derive(Component)]
+pub struct SkillButton (pub String);
+
+// Resources
+// =========================================================================
+
+#[derive(Resource)]
+pub struct Skills (pub Handle<SkillDatabase>);
+
+// Systems
+// =========================================================================
+
+pub fn load_skills (
+	mut commands : Commands,
+	assets : Res<AssetServer>,
+) {
+	commands.insert_resource(Skills(assets.load("data/base.skills.ron")));
+}
+
+/// Opens the skill menu with the skills of whoever's turn it is, or closes
+/// it if it's already open or another command is picked
+#[allow(clippy::too_many_arguments)]
+pub fn toggle_skill_menu (
+	mut commands : Commands,
+	button_query : Query<(&Interaction, &Name), (Changed<Interaction>, With<Button>, Without<Disabled>)>,
+	mut menu_query : Query<(Entity, &mut Style), With<SkillMenu>>,
+	member_query : Query<(&CombatStats, &KnownSkills), With<PartyCombatant>>,
+	turns : Res<TurnQueue>,
+	skills : Res<Skills>,
+	databases : Res<Assets<SkillDatabase>>,
+	pixel_font : Res<PixelFont>,
+) {
+	let Some(clicked) = button_query
+		.iter()
+		.find(|(interaction, _)| **interaction == Interaction::Clicked)
+		.map(|(_, name)| name.as_str()) else { return };
+
+	let Ok((menu, mut style)) = menu_query.get_single_mut() else { return };
+	commands.entity(menu).despawn_descendants();
+
+	// Picking any other command closes the menu too
+	if clicked != "skills" || style.display == Display::Flex {
+		style.display = Display::None;
+		return;
+	}
+
+	let Some((stats, known)) = turns.current.and_then(|id| member_query.get(id).ok()) else { return };
+	let Some(database) = databases.get(&skills.0) else { return };
+
+	style.display = Display::Flex;
+
+	commands.entity(menu).with_children(|parent| {
+		for id in &known.0 {
+			let Some(def) = database.0.get(id) else {
+				warn!("Unknown skill '{id}'");
+				continue;
+			};
+
+			spawn_menu_button(
+				parent,
+				&pixel_font,
+				format!("{} {}MP", def.name, def.cost),
+				"5B6EE1",
+				stats.mp >= def.cost,
+				SkillButton(id.clone()),
+			);
+		}
+	});
+}
+
+/// Spends the MP for a clicked skill and sends its effect to each of its
+/// targets. Skills used on an ally wait for the player to choose one first.
+#[allow(clippy::too_many_arguments)]
+pub fn use_skill (
+	mut fight_event : EventWriter<FightEvent>,
+	mut messages : EventWriter<CombatMessage>,
+	mut ally_chosen : EventReader<AllyChosen>,
+	button_query : Query<(&Interaction, &SkillButton), (Changed<Interaction>, Without<Disabled>)>,
+	mut party_query : Query<(Entity, &Name, &mut CombatStats), (With<PartyCombatant>, Without<Enemy>)>,
+	enemy_query : Query<(Entity, &CombatStats), (With<Enemy>, Without<PartyCombatant>)>,
+	turns : Res<TurnQueue>,
+	mut target : ResMut<CombatTarget>,
+	skills : Res<Skills>,
+	databases : Res<Assets<SkillDatabase>>,
+	state : Res<State<CombatState>>,
+) {
+	if state.current() != &CombatState::PlayerTurn { return; }
+
+	let chosen = ally_chosen.iter().find_map(|AllyChosen(action)| match action {
+		AllyAction::Skill(id) => Some(id.clone()),
+		_ => None,
+	});
+
+	let clicked = button_query
+		.iter()
+		.find(|(interaction, _)| **interaction == Interaction::Clicked)
+		.map(|(_, button)| button.0.clone());
+
+	let Some(id) = chosen.clone().or(clicked) else { return };
+	let Some(def) = databases.get(&skills.0).and_then(|db| db.0.get(&id)) else { return };
+	let Some(caster) = turns.current else { return };
+
+	if def.target == SkillTarget::Ally && chosen.is_none() {
+		messages.send(CombatMessage(format!("Choose who to use {} on.", def.name)));
+		target.choosing = Some(AllyAction::Skill(id));
+		return;
+	}
+
+	let selected = if chosen.is_some() { target.ally } else { target.enemy };
+
+	let targets = resolve_targets(
+		def.target,
+		caster,
+		selected,
+		party_query.iter().map(|(id, _, stats)| (id, stats)),
+		enemy_query.iter(),
+	);
+
+	if targets.is_empty() { return; }
+
+	let Ok((_, name, mut stats)) = party_query.get_mut(caster) else { return };
+	if stats.mp < def.cost { return; }
+	stats.mp -= def.cost;
+
+	messages.send(CombatMessage(format!("{name} uses {}!", def.name)));
+
+	for target in targets {
+		fight_event.send(FightEvent {
+			attacker: caster,
+			target,
+			amount: def.power,
+			effect: def.effect,
+			status: def.status,
+			next_state: CombatState::PlayerAttack,
+		});
+	}
+}
+
+pub fn close_skill_menu (
+	mut commands : Commands,
+	mut menu_query : Query<(Entity, &mut Style), With<SkillMenu>>,
+) {
+	for (menu, mut style) in &mut menu_query {
+		commands.entity(menu).despawn_descendants();
+		style.display = Display::None;
+	}
+}
+
+// Helpers
+// =========================================================================
+
+/// Who a skill or item used by `user` lands on, given the enemy or ally the
+/// player has targeted and everyone's stats
+pub fn resolve_targets<'a> (
+	target : SkillTarget,
+	user : Entity,
+	selected : Option<Entity>,
+	party : impl Iterator<Item = (Entity, &'a CombatStats)>,
+	enemies : impl Iterator<Item = (Entity, &'a CombatStats)>,
+) -> Vec<Entity> {
+	match target {
+		SkillTarget::Single => selected.into_iter().collect(),
+		SkillTarget::All => enemies
+			.filter(|(_, stats)| stats.health > 0)
+			.map(|(id, _)| id)
+			.collect(),
+		SkillTarget::Caster => vec![user],
+		SkillTarget::Ally => party
+			.filter(|(id, stats)| Some(*id) == selected && stats.health > 0)
+			.map(|(id, _)| id)
+			.collect(),
+		SkillTarget::Party => party
+			.filter(|(_, stats)| stats.health > 0)
+			.map(|(id, _)| id)
+			.collect(),
+	}
+}
+
+/// Adds a button to a menu opened from the combat menu, like the skill
+/// menu. Buttons that can't be used are greyed out and disabled.
+pub fn spawn_menu_button (
+	parent : &mut ChildBuilder,
+	pixel_font : &PixelFont,
+	label : String,
+	color : &str,
+	usable : bool,
+	button : impl Bundle,
+) {
+	let mut entity = parent.spawn((
+		ButtonBundle {
+			style: Style {
+				padding: UiRect::new(
+					Val::Px(15.), Val::Px(15.),
+					Val::Px(5.), Val::Px(5.),
+				),
+				margin: UiRect::bottom(Val::Px(5.)),
+				..default()
+			},
+			background_color: Color::hex(if usable { color } else { "6B6B6B" }).unwrap().into(),
+			..default()
+		},
+		button,
+	));
+
+	if !usable { entity.insert(Disabled); }
+
+	entity.with_children(|parent| {
+		parent.spawn(TextBundle::from_section(
+			label,
+			TextStyle {
+				font: pixel_font.0.clone(),
+				font_size: 30.,
+				color: Color::WHITE,
+			},
+		));
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stats (health : isize) -> CombatStats {
+		CombatStats { max_health: 10, health, attack: 1, defence: 1, speed: 1, max_mp: 0, mp: 0 }
+	}
+
+	#[test]
+	fn ally_skills_land_on_the_chosen_member () {
+		let (caster, hurt, chosen, fallen, enemy) = (
+			Entity::from_raw(0), Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3), Entity::from_raw(4),
+		);
+		let party = [(caster, stats(10)), (hurt, stats(1)), (chosen, stats(8)), (fallen, stats(0))];
+		let enemies = [(enemy, stats(10))];
+
+		let targets = |selected| resolve_targets(
+			SkillTarget::Ally,
+			caster,
+			selected,
+			party.iter().map(|(id, stats)| (*id, stats)),
+			enemies.iter().map(|(id, stats)| (*id, stats)),
+		);
+
+		assert_eq!(targets(Some(chosen)), vec![chosen]);
+		assert_eq!(targets(Some(caster)), vec![caster]);
+		assert!(targets(Some(fallen)).is_empty());
+		assert!(targets(Some(enemy)).is_empty());
+		assert!(targets(None).is_empty());
+	}
+}
@@ -0,0 +1,423 @@
+use std::mem::discriminant;
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::combat::{battle_outcome, CombatState, CombatStats, Enemy, knocked_out_message};
+use crate::combat::log::CombatMessage;
+use crate::combat::popups::{Popup, PopupEvent};
+use crate::combat::turns::TurnQueue;
+use crate::core::assets::{spawn_tilesheet_sprite_with_size, Tilesheet};
+use crate::TILE_SIZE;
+
+// Data
+// =========================================================================
+
+/// Something lasting that a skill or attack leaves on a combatant
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+	/// Loses this much health at the start of each turn
+	Poison (isize),
+	/// Regains this much health at the start of each turn
+	Regen (isize),
+	/// Skips turns until woken up by taking damage
+	Sleep,
+	/// Skips turns
+	Stun,
+	/// Raises a stat by this much, or lowers it if negative
+	Modifier (Stat, isize),
+	/// Takes less damage from attacks
+	Guard,
+}
+
+/// A stat that statuses can raise or lower
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+	Attack,
+	Defence,
+	Speed,
+}
+
+/// A status and how many of the combatant's turns it lasts for
+///
+/// ```ron
+/// (status: Poison(1), turns: 5)
+/// ```
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusEffect {
+	pub status : Status,
+	pub turns : u32,
+}
+
+impl Status {
+	/// Whether two statuses would replace one another rather than stack
+	fn same_kind (&self, other : &Status) -> bool {
+		match (self, other) {
+			(Status::Modifier(a, _), Status::Modifier(b, _)) => a == b,
+			_ => discriminant(self) == discriminant(other),
+		}
+	}
+
+	/// Whether the status stays on after the battle is over
+	pub fn lingers (&self) -> bool {
+		matches!(self, Status::Poison(_))
+	}
+
+	/// Whether the status costs the combatant their turn
+	fn skips_turn (&self) -> bool {
+		matches!(self, Status::Sleep | Status::Stun)
+	}
+
+	/// What the battle log says when the status is put on `name`
+	pub fn message (&self, name : &str) -> String {
+		match self {
+			Status::Poison(_) => format!("{name} is poisoned."),
+			Status::Regen(_) => format!("{name} starts to regenerate."),
+			Status::Sleep => format!("{name} falls asleep."),
+			Status::Stun => format!("{name} is stunned."),
+			Status::Modifier(stat, amount) => format!(
+				"{name}'s {} {}.",
+				stat.label(),
+				if *amount >= 0 { "rises" } else { "falls" },
+			),
+			Status::Guard => format!("{name} defends."),
+		}
+	}
+
+	/// Tilesheet index and tint of the icon shown beside the combatant
+	fn icon (&self) -> (usize, Color) {
+		match self {
+			Status::Poison(_) => (721, Color::hex("6ED57E").unwrap()),
+			Status::Regen(_) => (529, Color::WHITE),
+			Status::Sleep => (978, Color::hex("5B6EE1").unwrap()),
+			Status::Stun => (1007, Color::hex("EAB644").unwrap()),
+			Status::Modifier(_, amount) if *amount >= 0 => (1003, Color::hex("6ED57E").unwrap()),
+			Status::Modifier(..) => (1005, Color::hex("D5543B").unwrap()),
+			Status::Guard => (184, Color::WHITE),
+		}
+	}
+
+	/// Adds the status's stat changes on to `stats`, or takes them back off
+	fn modify (&self, stats : &mut CombatStats, apply : bool) {
+		let Status::Modifier(stat, amount) = *self else { return };
+		let amount = if apply { amount } else { -amount };
+
+		match stat {
+			Stat::Attack => stats.attack += amount,
+			Stat::Defence => stats.defence += amount,
+			Stat::Speed => stats.speed += amount,
+		}
+	}
+}
+
+impl Stat {
+	fn label (&self) -> &'static str {
+		match self {
+			Stat::Attack => "attack",
+			Stat::Defence => "defence",
+			Stat::Speed => "speed",
+		}
+	}
+}
+
+// Components
+// =========================================================================
+
+/// The statuses on a combatant. Stat modifiers are applied to the
+/// combatant's `CombatStats` while they last.
+#[derive(Component, Debug, Clone, Default)]
+pub struct StatusEffects (pub Vec<StatusEffect>);
+
+impl StatusEffects {
+	/// Puts a status on the combatant, replacing any of the same kind
+	pub fn add (&mut self, effect : StatusEffect, stats : &mut CombatStats) {
+		self.0.retain(|old| {
+			let replaced = old.status.same_kind(&effect.status);
+			if replaced { old.status.modify(stats, false); }
+			!replaced
+		});
+
+		effect.status.modify(stats, true);
+		self.0.push(effect);
+	}
+
+	/// Applies each status for the start of the combatant's turn and counts
+	/// down how long they have left. Returns the status that costs the
+	/// combatant their turn, if any.
+	pub fn tick (&mut self, stats : &mut CombatStats) -> Option<Status> {
+		let skip = self.0
+			.iter()
+			.map(|effect| effect.status)
+			.find(Status::skips_turn);
+
+		for effect in &mut self.0 {
+			match effect.status {
+				Status::Poison(amount) => stats.health = (stats.health - amount).max(0),
+				Status::Regen(amount) => stats.health = (stats.health + amount).min(stats.max_health),
+				_ => {},
+			}
+
+			effect.turns = effect.turns.saturating_sub(1);
+		}
+
+		self.0.retain(|effect| {
+			if effect.turns == 0 { effect.status.modify(stats, false); }
+			effect.turns > 0
+		});
+
+		skip
+	}
+
+	/// Whether the combatant is defending themselves
+	pub fn guarding (&self) -> bool {
+		self.0.iter().any(|effect| effect.status == Status::Guard)
+	}
+
+	/// Ends any sleep, for when the combatant is hurt. Returns true if they
+	/// were asleep.
+	pub fn wake (&mut self) -> bool {
+		let asleep = self.0.len();
+		self.0.retain(|effect| effect.status != Status::Sleep);
+		self.0.len() != asleep
+	}
+
+	/// Removes every status, taking off their stat changes
+	pub fn clear (&mut self, stats : &mut CombatStats) {
+		for effect in self.0.drain(..) {
+			effect.status.modify(stats, false);
+		}
+	}
+}
+
+/// A status icon, a child of the combatant it's on
+#[derive(Component)]
+pub struct StatusIcon;
+
+// Systems
+// =========================================================================
+
+/// Ticks the statuses of whoever's turn is starting, skipping the turn if
+/// they're asleep, stunned or knocked out by poison
+pub fn tick_statuses (
+	turns : Res<TurnQueue>,
+	mut state : ResMut<State<CombatState>>,
+	mut messages : EventWriter<CombatMessage>,
+	mut popups : EventWriter<PopupEvent>,
+	mut query : Query<(&Name, &mut CombatStats, &mut StatusEffects, &mut Visibility, Option<&Enemy>)>,
+) {
+	let Some(current) = turns.current else { return };
+	let Ok((name, mut stats, mut statuses, mut visibility, enemy)) = query.get_mut(current) else { return };
+	if statuses.0.is_empty() { return; }
+
+	let health = stats.health;
+	let skip = statuses.tick(&mut stats);
+	let change = stats.health - health;
+	let knocked_out = stats.health == 0;
+
+	if change < 0 {
+		messages.send(CombatMessage(format!("{name} takes {} poison damage.", -change)));
+		popups.send(PopupEvent { target: current, popup: Popup::Damage(-change) });
+	} else if change > 0 {
+		messages.send(CombatMessage(format!("{name} regenerates {change} HP.")));
+		popups.send(PopupEvent { target: current, popup: Popup::Heal(change) });
+	}
+
+	if knocked_out {
+		statuses.clear(&mut stats);
+		visibility.is_visible = enemy.is_none();
+		messages.send(CombatMessage(knocked_out_message(name, enemy.is_some())));
+	} else {
+		match skip {
+			Some(Status::Sleep) => messages.send(CombatMessage(format!("{name} is fast asleep."))),
+			Some(_) => messages.send(CombatMessage(format!("{name} can't move!"))),
+			None => {},
+		}
+	}
+
+	if let Some(outcome) = battle_outcome(query.iter().map(|(_, stats, _, _, enemy)| (stats.health, enemy.is_some()))) {
+		state.set(outcome).expect("Failed to set exit state");
+	} else if skip.is_some() || knocked_out {
+		state.set(CombatState::NextTurn).expect("Failed to set next turn state");
+	}
+}
+
+/// Redraws the icons beside a combatant whenever their statuses change
+pub fn update_status_icons (
+	mut commands : Commands,
+	tilesheet : Res<Tilesheet>,
+	query : Query<(Entity, &StatusEffects), Changed<StatusEffects>>,
+	icon_query : Query<(Entity, &Parent), With<StatusIcon>>,
+) {
+	let size = TILE_SIZE * 0.35;
+
+	for (id, statuses) in &query {
+		for (icon, parent) in &icon_query {
+			if parent.get() == id {
+				commands.entity(icon).despawn_recursive();
+			}
+		}
+
+		// A column down the right of the combatant
+		let icons : Vec<Entity> = statuses.0
+			.iter()
+			.enumerate()
+			.map(|(i, effect)| {
+				let (index, tint) = effect.status.icon();
+				let icon = spawn_tilesheet_sprite_with_size(
+					&mut commands,
+					&tilesheet,
+					index,
+					Vec3::new(TILE_SIZE * 0.65, TILE_SIZE * 0.35 - i as f32 * size, 1.),
+					Some(tint),
+					Some(Vec2::splat(size)),
+				);
+
+				commands.entity(icon).insert(StatusIcon);
+				icon
+			})
+			.collect();
+
+		commands.entity(id).push_children(&icons);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stats () -> CombatStats {
+		CombatStats { max_health: 20, health: 10, attack: 5, defence: 5, speed: 5, max_mp: 0, mp: 0 }
+	}
+
+	fn effect (status : Status, turns : u32) -> StatusEffect {
+		StatusEffect { status, turns }
+	}
+
+	// Adding
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn same_kind_refreshes () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+
+		statuses.add(effect(Status::Poison(1), 2), &mut stats);
+		statuses.add(effect(Status::Poison(3), 5), &mut stats);
+
+		assert_eq!(statuses.0, [effect(Status::Poison(3), 5)]);
+	}
+
+	#[test]
+	fn different_kinds_stack () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+
+		statuses.add(effect(Status::Poison(1), 2), &mut stats);
+		statuses.add(effect(Status::Sleep, 2), &mut stats);
+		statuses.add(effect(Status::Modifier(Stat::Attack, 2), 3), &mut stats);
+		statuses.add(effect(Status::Modifier(Stat::Defence, -1), 3), &mut stats);
+
+		assert_eq!(statuses.0.len(), 4);
+		assert_eq!((stats.attack, stats.defence), (7, 4));
+	}
+
+	#[test]
+	fn modifiers_replace_rather_than_add_up () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+
+		statuses.add(effect(Status::Modifier(Stat::Speed, 3), 3), &mut stats);
+		statuses.add(effect(Status::Modifier(Stat::Speed, -2), 3), &mut stats);
+
+		assert_eq!(statuses.0, [effect(Status::Modifier(Stat::Speed, -2), 3)]);
+		assert_eq!(stats.speed, 3);
+	}
+
+	// Ticking
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn statuses_count_down_and_wear_off () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+		statuses.add(effect(Status::Modifier(Stat::Attack, 2), 2), &mut stats);
+
+		statuses.tick(&mut stats);
+		assert_eq!(statuses.0[0].turns, 1);
+		assert_eq!(stats.attack, 7);
+
+		statuses.tick(&mut stats);
+		assert!(statuses.0.is_empty());
+		assert_eq!(stats.attack, 5);
+	}
+
+	#[test]
+	fn poison_and_regen_stay_in_bounds () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+
+		statuses.add(effect(Status::Poison(4), 5), &mut stats);
+		statuses.tick(&mut stats);
+		assert_eq!(stats.health, 6);
+
+		statuses.add(effect(Status::Poison(50), 5), &mut stats);
+		statuses.tick(&mut stats);
+		assert_eq!(stats.health, 0);
+
+		let mut stats = CombatStats { health: 18, ..stats };
+		let mut statuses = StatusEffects::default();
+		statuses.add(effect(Status::Regen(5), 5), &mut stats);
+		statuses.tick(&mut stats);
+		assert_eq!(stats.health, 20);
+	}
+
+	#[test]
+	fn sleep_and_stun_skip_turns () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+		statuses.add(effect(Status::Stun, 1), &mut stats);
+
+		// The last turn of a status still counts
+		assert_eq!(statuses.tick(&mut stats), Some(Status::Stun));
+		assert_eq!(statuses.tick(&mut stats), None);
+
+		statuses.add(effect(Status::Poison(1), 3), &mut stats);
+		assert_eq!(statuses.tick(&mut stats), None);
+	}
+
+	// Waking and clearing
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn waking_only_ends_sleep () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+		statuses.add(effect(Status::Sleep, 3), &mut stats);
+		statuses.add(effect(Status::Stun, 3), &mut stats);
+
+		assert!(statuses.wake());
+		assert_eq!(statuses.0, [effect(Status::Stun, 3)]);
+		assert!(!statuses.wake());
+	}
+
+	#[test]
+	fn clearing_takes_off_modifiers () {
+		let mut stats = stats();
+		let mut statuses = StatusEffects::default();
+		statuses.add(effect(Status::Modifier(Stat::Defence, 3), 3), &mut stats);
+		statuses.add(effect(Status::Guard, 1), &mut stats);
+		statuses.add(effect(Status::Poison(1), 3), &mut stats);
+
+		statuses.clear(&mut stats);
+
+		assert!(statuses.0.is_empty());
+		assert!(!statuses.guarding());
+		assert_eq!(stats.defence, 5);
+	}
+
+	#[test]
+	fn only_poison_lingers_after_battle () {
+		assert!(Status::Poison(1).lingers());
+		assert!(!Status::Regen(1).lingers());
+		assert!(!Status::Sleep.lingers());
+		assert!(!Status::Modifier(Stat::Attack, 1).lingers());
+	}
+}
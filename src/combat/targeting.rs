@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+use crate::combat::{CombatStats, Enemy, PartyCombatant};
+use crate::core::assets::{spawn_tilesheet_sprite, Tilesheet};
+use crate::TILE_SIZE;
+
+// Resources
+// =========================================================================
+
+/// Who the player's actions are aimed at
+#[derive(Resource, Default)]
+pub struct CombatTarget {
+	pub enemy : Option<Entity>,
+	/// The party member picked for a skill or item used on an ally
+	pub ally : Option<Entity>,
+	/// The skill or item waiting on the player to choose an ally, while the
+	/// target moves between party members instead of enemies
+	pub choosing : Option<AllyAction>,
+}
+
+impl CombatTarget {
+	/// Whoever the cursor is over
+	pub fn current (&self) -> Option<Entity> {
+		if self.choosing.is_some() { self.ally } else { self.enemy }
+	}
+}
+
+/// A skill or item that targets an ally, by id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllyAction {
+	Skill (String),
+	Item (String),
+}
+
+// Events
+// =========================================================================
+
+/// Sent once the player has chosen who to use an [`AllyAction`] on, which
+/// is left in [`CombatTarget::ally`]
+pub struct AllyChosen (pub AllyAction);
+
+// Components
+// =========================================================================
+
+#[derive(Component)]
+pub struct TargetCursor;
+
+// Systems
+// =========================================================================
+
+pub fn spawn_target_cursor (
+	mut commands : Commands,
+	tilesheet : Res<Tilesheet>,
+) {
+	// Down chevron
+	let id = spawn_tilesheet_sprite(
+		&mut commands,
+		&tilesheet,
+		1005,
+		Vec3::ZERO,
+		None,
+	);
+
+	commands.entity(id).insert((
+		Name::new("Target Cursor"),
+		TargetCursor,
+	));
+}
+
+pub fn despawn_target_cursor (
+	mut commands : Commands,
+	query : Query<Entity, With<TargetCursor>>,
+	mut target : ResMut<CombatTarget>,
+) {
+	for id in &query {
+		commands.entity(id).despawn_recursive();
+	}
+
+	*target = CombatTarget::default();
+}
+
+/// Moves the target between living enemies with the arrow keys, or to the
+/// enemy that was clicked on. Falls back to the leftmost enemy when there
+/// is no target or it has been defeated.
+///
+/// While choosing an ally it moves between living party members instead,
+/// starting on the one with the least health left. Confirming, or clicking
+/// a member, sends [`AllyChosen`]. Escape or picking a command cancels.
+#[allow(clippy::too_many_arguments)]
+pub fn select_target (
+	keyboard : Res<Input<KeyCode>>,
+	mouse : Res<Input<MouseButton>>,
+	windows : Res<Windows>,
+	camera_query : Query<(&Camera, &GlobalTransform)>,
+	button_query : Query<&Interaction, (Changed<Interaction>, With<Button>, With<Name>)>,
+	combatant_query : Query<(Entity, &Transform, &CombatStats, Option<&PartyCombatant>), Or<(With<Enemy>, With<PartyCombatant>)>>,
+	mut ally_chosen : EventWriter<AllyChosen>,
+	mut target : ResMut<CombatTarget>,
+) {
+	if target.choosing.is_some() && (
+		keyboard.just_pressed(KeyCode::Escape)
+		|| button_query.iter().any(|interaction| *interaction == Interaction::Clicked)
+	) {
+		target.choosing = None;
+	}
+
+	let choosing = target.choosing.is_some();
+
+	let mut candidates : Vec<(Entity, Vec3, f32)> = combatant_query
+		.iter()
+		.filter(|(_, _, stats, member)| stats.health > 0 && member.is_some() == choosing)
+		.map(|(id, transform, stats, _)| (id, transform.translation, stats.health_left()))
+		.collect();
+	candidates.sort_by(|a, b| a.1.x.total_cmp(&b.1.x));
+
+	let selected = if choosing { target.ally } else { target.enemy };
+	let current = selected.and_then(|id| candidates.iter().position(|(candidate, ..)| *candidate == id));
+
+	let fallback = if choosing {
+		candidates
+			.iter()
+			.enumerate()
+			.min_by(|(_, a), (_, b)| a.2.total_cmp(&b.2))
+			.map(|(index, _)| index)
+	} else {
+		(!candidates.is_empty()).then_some(0)
+	};
+
+	let Some(mut next) = current.or(fallback) else {
+		if choosing { target.ally = None; } else { target.enemy = None; }
+		return;
+	};
+
+	if keyboard.any_just_pressed([KeyCode::Left, KeyCode::A]) {
+		next = (next + candidates.len() - 1) % candidates.len();
+	}
+
+	if keyboard.any_just_pressed([KeyCode::Right, KeyCode::D]) {
+		next = (next + 1) % candidates.len();
+	}
+
+	let mut confirmed = keyboard.any_just_pressed([KeyCode::Return, KeyCode::Space]);
+
+	if mouse.just_pressed(MouseButton::Left) {
+		if let Some(cursor) = cursor_to_world(&windows, &camera_query) {
+			let clicked = candidates.iter().position(|(_, translation, _)| {
+				(translation.truncate() - cursor).abs().max_element() <= TILE_SIZE * 0.5
+			});
+
+			if let Some(clicked) = clicked {
+				next = clicked;
+				confirmed = true;
+			}
+		}
+	}
+
+	let next = Some(candidates[next].0);
+
+	if !choosing {
+		if target.enemy != next { target.enemy = next; }
+		return;
+	}
+
+	if target.ally != next { target.ally = next; }
+
+	if confirmed {
+		if let Some(action) = target.choosing.take() {
+			ally_chosen.send(AllyChosen(action));
+		}
+	}
+}
+
+/// Stops choosing an ally once the player's turn is over
+pub fn stop_choosing_ally (
+	mut target : ResMut<CombatTarget>,
+) {
+	target.choosing = None;
+}
+
+pub fn move_target_cursor (
+	target : Res<CombatTarget>,
+	combatant_query : Query<&Transform, (With<CombatStats>, Without<TargetCursor>)>,
+	mut cursor_query : Query<(&mut Transform, &mut Visibility), With<TargetCursor>>,
+) {
+	let Ok((mut cursor, mut visibility)) = cursor_query.get_single_mut() else { return };
+	let combatant = target.current().and_then(|id| combatant_query.get(id).ok());
+
+	visibility.is_visible = combatant.is_some();
+
+	if let Some(combatant) = combatant {
+		cursor.translation = combatant.translation + Vec3::new(0., TILE_SIZE, 1.);
+	}
+}
+
+// Helpers
+// =========================================================================
+
+fn cursor_to_world (
+	windows : &Windows,
+	camera_query : &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+	let cursor = windows.get_primary()?.cursor_position()?;
+	let (camera, transform) = camera_query.get_single().ok()?;
+
+	camera
+		.viewport_to_world(transform, cursor)
+		.map(|ray| ray.origin.truncate())
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use crate::combat::{CombatState, CombatStats, Enemy};
+use crate::core::settings::{BattleMode, Settings};
+
+/// The most actions anyone can take in a round, however fast they are
+const MAX_ACTIONS_PER_ROUND : isize = 3;
+
+// Resources
+// =========================================================================
+
+/// Who acts when. Each round everyone still standing is ordered by speed,
+/// and anyone at least twice as fast as the slowest combatant gets an extra
+/// action for each multiple, spread out over the round.
+///
+/// In active time battles there are no rounds, combatants join the queue as
+/// their gauges fill instead.
+#[derive(Resource, Default)]
+pub struct TurnQueue {
+	/// Whoever is taking their turn
+	pub current : Option<Entity>,
+	/// Everyone still to act this round, in order
+	pub upcoming : VecDeque<Entity>,
+}
+
+impl TurnQueue {
+	/// Orders a new round from each combatant's speed
+	pub fn plan_round (&mut self, combatants : &[(Entity, isize)]) {
+		let slowest = combatants
+			.iter()
+			.map(|(_, speed)| (*speed).max(1))
+			.min()
+			.unwrap_or(1);
+
+		let mut actions = Vec::new();
+		for &(id, speed) in combatants {
+			let count = (speed.max(1) / slowest).clamp(1, MAX_ACTIONS_PER_ROUND);
+
+			for action in 0..count {
+				actions.push((action as f32 / count as f32, speed, id));
+			}
+		}
+
+		// Earliest in the round first, and the faster of two acting at the
+		// same point
+		actions.sort_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)));
+
+		self.upcoming = actions.into_iter().map(|(_, _, id)| id).collect();
+	}
+
+	/// Hands the turn to whoever is next, dropping anyone no longer
+	/// `standing`. Once everyone has acted a new round is planned from
+	/// `next_round`, if it gives one.
+	pub fn advance (
+		&mut self,
+		standing : impl Fn(&Entity) -> bool,
+		next_round : impl FnOnce() -> Option<Vec<(Entity, isize)>>,
+	) -> Option<Entity> {
+		self.upcoming.retain(standing);
+
+		if self.upcoming.is_empty() {
+			if let Some(combatants) = next_round() {
+				self.plan_round(&combatants);
+			}
+		}
+
+		self.current = self.upcoming.pop_front();
+		self.current
+	}
+}
+
+// Systems
+// =========================================================================
+
+/// Hands the turn to whoever is next in the queue, planning a new round
+/// once everyone has acted
+pub fn next_turn (
+	mut queue : ResMut<TurnQueue>,
+	mut state : ResMut<State<CombatState>>,
+	settings : Res<Settings>,
+	query : Query<(Entity, &CombatStats, Option<&Enemy>)>,
+) {
+	let standing = |id : &Entity| query.get(*id).is_ok_and(|(_, stats, _)| stats.health > 0);
+
+	// Active time battles have no rounds, gauges fill the queue instead
+	let next_round = || (settings.battle_mode == BattleMode::Turns).then(|| query
+		.iter()
+		.filter(|(id, _, _)| standing(id))
+		.map(|(id, stats, _)| (id, stats.speed))
+		.collect()
+	);
+
+	// Nobody to act until the combatants have spawned, or until a gauge
+	// fills
+	let Some(next) = queue.advance(standing, next_round) else { return };
+	let Ok((_, _, enemy)) = query.get(next) else { return };
+
+	state.set(match enemy {
+		Some(_) => CombatState::EnemyTurn(false),
+		None => CombatState::PlayerTurn,
+	}).expect("Failed to set turn state");
+}
+
+pub fn clear_turn_queue (
+	mut queue : ResMut<TurnQueue>,
+) {
+	queue.current = None;
+	queue.upcoming.clear();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ids<const N : usize> () -> [Entity; N] {
+		std::array::from_fn(|i| Entity::from_raw(i as u32))
+	}
+
+	fn planned (combatants : &[(Entity, isize)]) -> Vec<Entity> {
+		let mut queue = TurnQueue::default();
+		queue.plan_round(combatants);
+		queue.upcoming.into()
+	}
+
+	// Planning
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn faster_combatants_act_first () {
+		let [a, b, c] = ids();
+		assert_eq!(planned(&[(a, 6), (b, 10), (c, 8)]), [b, c, a]);
+	}
+
+	#[test]
+	fn ties_keep_their_order () {
+		let [a, b, c] = ids();
+
+		assert_eq!(planned(&[(a, 5), (b, 5), (c, 5)]), [a, b, c]);
+		assert_eq!(planned(&[(c, 5), (a, 5), (b, 7)]), [b, c, a]);
+	}
+
+	#[test]
+	fn twice_as_fast_acts_twice () {
+		let [fast, slow] = ids();
+		assert_eq!(planned(&[(slow, 5), (fast, 10)]), [fast, slow, fast]);
+	}
+
+	#[test]
+	fn extra_actions_are_capped () {
+		let [fast, slow] = ids();
+		let round = planned(&[(fast, 100), (slow, 1)]);
+
+		assert_eq!(round.len(), MAX_ACTIONS_PER_ROUND as usize + 1);
+		assert_eq!(round.iter().filter(|&&id| id == fast).count(), MAX_ACTIONS_PER_ROUND as usize);
+	}
+
+	#[test]
+	fn speeds_below_1_count_as_1 () {
+		let [a, b] = ids();
+
+		assert_eq!(planned(&[(a, -3), (b, 0)]), [b, a]);
+		assert_eq!(planned(&[(a, 0), (b, 2)]), [b, a, b]);
+	}
+
+	#[test]
+	fn nobody_to_plan () {
+		assert!(planned(&[]).is_empty());
+	}
+
+	// Advancing
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn fallen_combatants_are_skipped () {
+		let [a, b, c] = ids();
+		let mut queue = TurnQueue::default();
+		queue.plan_round(&[(a, 10), (b, 8), (c, 6)]);
+
+		assert_eq!(queue.advance(|_| true, || None), Some(a));
+		assert_eq!(queue.advance(|&id| id != b, || None), Some(c));
+		assert_eq!(queue.current, Some(c));
+	}
+
+	#[test]
+	fn next_round_is_planned_once_everyone_has_acted () {
+		let [a, b] = ids();
+		let mut queue = TurnQueue::default();
+		queue.plan_round(&[(a, 10), (b, 8)]);
+
+		queue.advance(|_| true, || panic!("the round isn't over"));
+		queue.advance(|_| true, || panic!("the round isn't over"));
+
+		// b sped up, and a fell, over the last round
+		assert_eq!(queue.advance(|_| true, || Some(vec![(b, 20)])), Some(b));
+		assert!(queue.upcoming.is_empty());
+	}
+
+	#[test]
+	fn no_round_leaves_nobody_to_act () {
+		let [a] = ids();
+		let mut queue = TurnQueue { current: Some(a), upcoming: VecDeque::new() };
+
+		assert_eq!(queue.advance(|_| true, || None), None);
+		assert_eq!(queue.current, None);
+	}
+}
@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 use crate::TILE_SIZE;
 
+pub const TILESHEET_COLUMNS : usize = 49;
+pub const TILESHEET_ROWS : usize = 22;
+
 // PLugin
 // =========================================================================
 
@@ -18,7 +21,7 @@ impl Plugin for AssetsPlugin {
 // =========================================================================
 
 #[derive(Resource)]
-pub struct Tilesheet (Handle<TextureAtlas>);
+pub struct Tilesheet (pub Handle<TextureAtlas>);
 
 #[derive(Resource)]
 pub struct PixelFont (pub Handle<Font>);
@@ -35,8 +38,8 @@ fn load_assets (
 	let tilesheet_atlas = TextureAtlas::from_grid(
 		tilesheet_image,
 		Vec2::splat(16.),
-		49,
-		22,
+		TILESHEET_COLUMNS,
+		TILESHEET_ROWS,
 		Some(Vec2::splat(1.)),
 		None,
 	);
@@ -53,7 +56,7 @@ fn load_assets (
 
 pub fn spawn_tilesheet_sprite (
 	commands : &mut Commands,
-	tilesheet : &Res<Tilesheet>,
+	tilesheet : &Tilesheet,
 	index : usize,
 	translation : Vec3,
 	tint : Option<Color>,
@@ -70,7 +73,7 @@ pub fn spawn_tilesheet_sprite (
 
 pub fn spawn_tilesheet_sprite_with_size (
 	commands : &mut Commands,
-	tilesheet : &Res<Tilesheet>,
+	tilesheet : &Tilesheet,
 	index : usize,
 	translation : Vec3,
 	tint : Option<Color>,
@@ -4,6 +4,7 @@ use bevy_kira_audio::AudioPlugin as KiraAudioPlugin;
 use rand::Rng;
 use crate::combat::{CombatState, FightEvent};
 use crate::GameState;
+use crate::leveling::LevelUpEvent;
 use crate::util::math::clamp01;
 
 // Plugin
@@ -20,6 +21,7 @@ impl Plugin for AudioPlugin {
 			.add_audio_channel::<SfxChannel>()
 			.add_startup_system_to_stage(StartupStage::PreStartup, load_audio)
 			.add_system(volume_control)
+			.add_system(play_level_up_sfx)
 			.add_system_set(
 				SystemSet::on_enter(GameState::Combat)
 					.with_system(start_combat_music)
@@ -66,6 +68,7 @@ pub struct SfxChannel;
 pub struct AudioState {
 	hit_sfx : Handle<AudioSource>,
 	success_sfx : Handle<AudioSource>,
+	level_up_sfx : Handle<AudioSource>,
 
 	combat_music : Handle<AudioSource>,
 	overworld_music : Handle<AudioSource>,
@@ -85,6 +88,7 @@ fn load_audio (
 ) {
 	let hit_sfx = assets.load("audio/sfx/hit.ogg");
 	let success_sfx = assets.load("audio/sfx/success.ogg");
+	let level_up_sfx = assets.load("audio/sfx/level-up.ogg");
 	let combat_music = assets.load("audio/music/Cruising-for-Goblins.ogg");
 	let overworld_music = assets.load("audio/music/Kirk-Osamayo-Video-Game-Snowy-Night.ogg");
 
@@ -97,6 +101,7 @@ fn load_audio (
 	commands.insert_resource::<AudioState>(AudioState {
 		hit_sfx,
 		success_sfx,
+		level_up_sfx,
 		combat_music,
 		overworld_music,
 
@@ -164,6 +169,16 @@ fn play_hit_sfx (
 	}
 }
 
+fn play_level_up_sfx (
+	channel : Res<AudioChannel<SfxChannel>>,
+	state : Res<AudioState>,
+	mut events : EventReader<LevelUpEvent>,
+) {
+	if events.iter().count() > 0 {
+		channel.play(state.level_up_sfx.clone());
+	}
+}
+
 fn play_success_sfx (
 	channel : Res<AudioChannel<SfxChannel>>,
 	state : Res<AudioState>,
@@ -0,0 +1,63 @@
+use std::marker::PhantomData;
+use bevy::asset::{Asset, AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::de::DeserializeOwned;
+
+// Loader
+// =========================================================================
+
+/// Loads game data assets from RON files. Each kind of data gets its own
+/// double extension, like `base.enemies.ron`, so files can be split up
+/// and hot reloaded without recompiling.
+pub struct RonLoader<T> {
+	extensions : &'static [&'static str],
+	marker : PhantomData<fn() -> T>,
+}
+
+impl<T> RonLoader<T> {
+	pub fn new (extensions : &'static [&'static str]) -> Self {
+		Self { extensions, marker: PhantomData }
+	}
+}
+
+impl<T : Asset + DeserializeOwned> AssetLoader for RonLoader<T> {
+	fn load<'a>(
+		&'a self,
+		bytes : &'a [u8],
+		load_context : &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let data : T = ron::de::from_bytes(bytes)?;
+			load_context.set_default_asset(LoadedAsset::new(data));
+			Ok(())
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		self.extensions
+	}
+}
+
+// Utilities
+// =========================================================================
+
+pub trait AddRonAsset {
+	/// Registers a data asset along with a loader for files ending in one
+	/// of `extensions`
+	fn add_ron_asset<T : Asset + DeserializeOwned> (
+		&mut self,
+		extensions : &'static [&'static str],
+	) -> &mut Self;
+}
+
+impl AddRonAsset for App {
+	fn add_ron_asset<T : Asset + DeserializeOwned> (
+		&mut self,
+		extensions : &'static [&'static str],
+	) -> &mut Self {
+		self
+			.add_asset::<T>()
+			.add_asset_loader(RonLoader::<T>::new(extensions))
+	}
+}
@@ -4,3 +4,5 @@ pub mod tilemap;
 pub mod transition;
 pub mod animator;
 pub mod audio;
+pub mod data;
+pub mod settings;
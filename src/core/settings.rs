@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+// Resources
+// =========================================================================
+
+/// Options the player can change from the main menu
+#[derive(Resource, Default, Debug)]
+pub struct Settings {
+	pub battle_mode : BattleMode,
+	pub defeat_mode : DefeatMode,
+}
+
+/// How turns are handed out in battle
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleMode {
+	/// Everyone takes turns in order of speed, one round at a time
+	#[default]
+	Turns,
+	/// Everyone fills a time gauge in real time and acts when it is full
+	Active,
+}
+
+impl BattleMode {
+	pub fn next (self) -> Self {
+		match self {
+			BattleMode::Turns => BattleMode::Active,
+			BattleMode::Active => BattleMode::Turns,
+		}
+	}
+
+	pub fn label (self) -> &'static str {
+		match self {
+			BattleMode::Turns => "Battles: Turns",
+			BattleMode::Active => "Battles: Active",
+		}
+	}
+}
+
+/// What happens when the whole party is knocked out
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefeatMode {
+	/// Go to the game over screen
+	#[default]
+	GameOver,
+	/// Wake up at the last healer visited, losing some gold
+	Respawn,
+}
+
+impl DefeatMode {
+	pub fn next (self) -> Self {
+		match self {
+			DefeatMode::GameOver => DefeatMode::Respawn,
+			DefeatMode::Respawn => DefeatMode::GameOver,
+		}
+	}
+
+	pub fn label (self) -> &'static str {
+		match self {
+			DefeatMode::GameOver => "Defeat: Game Over",
+			DefeatMode::Respawn => "Defeat: Respawn",
+		}
+	}
+}
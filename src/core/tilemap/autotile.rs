@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use crate::core::tilemap::TileFrame;
+
+// Assets
+// =========================================================================
+
+/// Named autotile rules, loaded from `*.autotiles.ron` files. Legend
+/// entries opt into a rule with `autotile=<name>`.
+///
+/// ```ron
+/// {
+///     "wall": (
+///         neighbours: Cardinal,
+///         default: (index: 12),
+///         pieces: {
+///             5: (index: 8, frames: [(index: 8), (index: 8, flip_y: true)]),
+///             10: (index: 8, turns: 1),
+///         },
+///     ),
+/// }
+/// ```
+#[derive(TypeUuid, Deserialize, Debug, Default)]
+#[uuid = "3b8f0f5c-8a41-4a8e-9f0e-2d7c4b1e6a52"]
+#[serde(transparent)]
+pub struct AutotileSet (pub HashMap<String, AutotileRules>);
+
+/// Picks a tile from which of its neighbours use the same rule
+#[derive(Deserialize, Debug)]
+pub struct AutotileRules {
+	pub neighbours : Neighbours,
+	/// Used for any arrangement of neighbours without a piece of its own
+	pub default : AutotilePiece,
+	/// Pieces keyed by the neighbour bitmask
+	pub pieces : HashMap<u8, AutotilePiece>,
+}
+
+/// Which neighbours make up the bitmask.
+///
+/// `Cardinal` is 4-bit: N = 1, E = 2, S = 4, W = 8.
+///
+/// `All` is 8-bit: N = 1, NE = 2, E = 4, SE = 8, S = 16, SW = 32, W = 64,
+/// NW = 128. Corners only count when both of the sides next to them are
+/// set, which leaves 47 distinct masks.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbours {
+	Cardinal,
+	All,
+}
+
+/// A tilesheet index, optionally mirrored and then turned clockwise in
+/// quarter turns, so one drawn corner can stand in for all four
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AutotilePiece {
+	pub index : usize,
+	#[serde(default)]
+	pub flip_x : bool,
+	#[serde(default)]
+	pub flip_y : bool,
+	#[serde(default)]
+	pub turns : u8,
+	/// Shown one after another when the tile is marked `animated`, each
+	/// with its own orientation, so they should all keep the piece's shape.
+	/// Pieces without frames stay still.
+	#[serde(default)]
+	pub frames : Vec<TileFrame>,
+}
+
+impl AutotileRules {
+	/// The piece for a tile, where `connected` says whether the neighbour at
+	/// a cell offset uses the same rule
+	pub fn piece (&self, connected : impl Fn(IVec2) -> bool) -> AutotilePiece {
+		let n = connected(IVec2::new(0, -1));
+		let e = connected(IVec2::new(1, 0));
+		let s = connected(IVec2::new(0, 1));
+		let w = connected(IVec2::new(-1, 0));
+
+		let mask = match self.neighbours {
+			Neighbours::Cardinal => bits(&[n, e, s, w]),
+			Neighbours::All => bits(&[
+				n,
+				n && e && connected(IVec2::new(1, -1)),
+				e,
+				s && e && connected(IVec2::new(1, 1)),
+				s,
+				s && w && connected(IVec2::new(-1, 1)),
+				w,
+				n && w && connected(IVec2::new(-1, -1)),
+			]),
+		};
+
+		self.pieces.get(&mask).unwrap_or(&self.default).clone()
+	}
+}
+
+impl AutotilePiece {
+	/// The transform a tile at `translation` needs to show this piece
+	pub fn transform (&self, translation : Vec3) -> Transform {
+		TileFrame {
+			index: self.index,
+			flip_x: self.flip_x,
+			flip_y: self.flip_y,
+			turns: self.turns,
+		}.transform(translation)
+	}
+}
+
+// Resources
+// =========================================================================
+
+/// The autotile rules maps are drawn with
+#[derive(Resource)]
+pub struct Autotiles (pub Handle<AutotileSet>);
+
+// Systems
+// =========================================================================
+
+pub fn load_autotiles (
+	mut commands : Commands,
+	assets : Res<AssetServer>,
+) {
+	commands.insert_resource(Autotiles(assets.load("data/base.autotiles.ron")));
+}
+
+// Helpers
+// =========================================================================
+
+fn bits (set : &[bool]) -> u8 {
+	set.iter()
+		.enumerate()
+		.fold(0, |mask, (bit, &set)| if set { mask | 1 << bit } else { mask })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rules (neighbours : Neighbours, pieces : &[(u8, usize)]) -> AutotileRules {
+		AutotileRules {
+			neighbours,
+			default: AutotilePiece { index: 99, ..default() },
+			pieces: pieces
+				.iter()
+				.map(|&(mask, index)| (mask, AutotilePiece { index, ..default() }))
+				.collect(),
+		}
+	}
+
+	/// The piece for the centre of a 3x3 grid, where `#` cells use the rule
+	fn piece (rules : &AutotileRules, grid : [&str; 3]) -> usize {
+		rules.piece(|offset| {
+			let cell = offset + IVec2::ONE;
+			grid[cell.y as usize].as_bytes()[cell.x as usize] == b'#'
+		}).index
+	}
+
+	#[test]
+	fn bits_set_in_order () {
+		assert_eq!(bits(&[]), 0);
+		assert_eq!(bits(&[true, false, true, false]), 5);
+		assert_eq!(bits(&[false; 8]), 0);
+		assert_eq!(bits(&[true; 8]), 255);
+	}
+
+	#[test]
+	fn base_rules_load () {
+		let text = std::fs::read_to_string("assets/data/base.autotiles.ron").unwrap();
+		let set : AutotileSet = ron::de::from_str(&text).unwrap();
+
+		assert_eq!(set.0["wall"].neighbours, Neighbours::Cardinal);
+		assert_eq!(set.0["water"].pieces[&31].frames.len(), 2);
+		assert_eq!(set.0["path"].neighbours, Neighbours::Cardinal);
+		assert_eq!(set.0["path"].pieces.len(), 15);
+	}
+
+	// Cardinal
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn straight_edge () {
+		let rules = rules(Neighbours::Cardinal, &[(5, 8), (10, 9)]);
+
+		assert_eq!(piece(&rules, [".#.", ".#.", ".#."]), 8);
+		assert_eq!(piece(&rules, ["...", "###", "..."]), 9);
+	}
+
+	#[test]
+	fn cardinal_ignores_corners () {
+		let rules = rules(Neighbours::Cardinal, &[(5, 8)]);
+		assert_eq!(piece(&rules, ["###", ".#.", "###"]), 8);
+	}
+
+	#[test]
+	fn isolated_tile_uses_the_default () {
+		let cardinal = rules(Neighbours::Cardinal, &[(0, 12)]);
+		let all = rules(Neighbours::All, &[(5, 8)]);
+
+		assert_eq!(piece(&cardinal, ["...", ".#.", "..."]), 12);
+		assert_eq!(piece(&all, ["...", ".#.", "..."]), 99);
+	}
+
+	// All
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn all_neighbours_set_every_bit () {
+		let rules = rules(Neighbours::All, &[(255, 253)]);
+		assert_eq!(piece(&rules, ["###", "###", "###"]), 253);
+	}
+
+	#[test]
+	fn inner_corner () {
+		// Everything but the north west corner
+		let rules = rules(Neighbours::All, &[(127, 256)]);
+		assert_eq!(piece(&rules, [".##", "###", "###"]), 256);
+	}
+
+	#[test]
+	fn straight_shore () {
+		// Water to the east, south and west, with the corners between them
+		let rules = rules(Neighbours::All, &[(124, 254)]);
+		assert_eq!(piece(&rules, ["...", "###", "###"]), 254);
+	}
+
+	#[test]
+	fn corners_need_both_sides () {
+		let rules = rules(Neighbours::All, &[(0, 1), (1, 2), (5, 3)]);
+
+		// A lone diagonal neighbour counts for nothing
+		assert_eq!(piece(&rules, ["..#", ".#.", "..."]), 1);
+		// Nor does one with only one of its sides
+		assert_eq!(piece(&rules, [".##", ".#.", "..."]), 2);
+		// Both sides set the corner as well
+		assert_eq!(piece(&rules, [".##", ".##", "..."]), 99);
+		assert_eq!(piece(&rules, [".#.", ".##", "..."]), 3);
+	}
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use crate::core::assets::{spawn_tilesheet_sprite, Tilesheet};
+use crate::core::tilemap::{ActiveMap, AnimatedTile, cell_to_translation, EncounterSpawner, Map, MapAsset, MapLayer, TileCollider, TileFrame, TileTags};
+use crate::core::tilemap::autotile::{Autotiles, AutotileSet};
+use crate::core::tilemap::grid::{map_size, translation_to_cell};
+
+/// Width and height of a chunk in cells
+pub const CHUNK_SIZE : i32 = 16;
+
+// Components
+// =========================================================================
+
+/// The chunks of tile sprites currently spawned under a `Map`
+#[derive(Component, Default)]
+pub struct MapChunks {
+	loaded : HashMap<IVec2, Entity>,
+}
+
+#[derive(Component)]
+pub struct MapChunk;
+
+// Systems
+// =========================================================================
+
+/// Spawns the chunks around the camera and despawns the ones that have
+/// gone well out of view, so only a screen's worth of tiles exist at once
+/// however big the map is.
+#[allow(clippy::too_many_arguments)]
+pub fn stream_chunks (
+	mut commands : Commands,
+	tilesheet : Res<Tilesheet>,
+	assets : Res<AssetServer>,
+	maps : Res<Assets<MapAsset>>,
+	autotile_sets : Res<Assets<AutotileSet>>,
+	autotiles : Res<Autotiles>,
+	active_map : Option<Res<ActiveMap>>,
+	mut map_query : Query<(Entity, &mut MapChunks), With<Map>>,
+	camera_query : Query<(&Transform, &OrthographicProjection), With<Camera>>,
+) {
+	let Some(map) = active_map.and_then(|active| maps.get(&active.0)) else { return };
+	let Ok((map_id, mut chunks)) = map_query.get_single_mut() else { return };
+	let Ok((camera, projection)) = camera_query.get_single() else { return };
+
+	// Wait for the autotile rules so tiles aren't drawn with the wrong
+	// pieces, but still draw the map if they failed to load
+	let autotile_set = autotile_sets.get(&autotiles.0);
+	if autotile_set.is_none() && assets.get_load_state(&autotiles.0) == LoadState::Loading { return; }
+
+	let half_view = Vec2::new(
+		projection.right - projection.left,
+		projection.top - projection.bottom,
+	) * projection.scale * 0.5;
+
+	let chunk_count = (map_size(map).as_ivec2() + (CHUNK_SIZE - 1)) / CHUNK_SIZE;
+	let visible = chunk_range(camera.translation, half_view, 0);
+	let keep = chunk_range(camera.translation, half_view, 1);
+
+	chunks.loaded.retain(|chunk, id| {
+		let in_range = chunk.cmpge(keep.0).all() && chunk.cmple(keep.1).all();
+		if !in_range { commands.entity(*id).despawn_recursive(); }
+		in_range
+	});
+
+	let layer_z = layer_z(map);
+
+	for y in visible.0.y.max(0)..=visible.1.y.min(chunk_count.y - 1) {
+		for x in visible.0.x.max(0)..=visible.1.x.min(chunk_count.x - 1) {
+			let chunk = IVec2::new(x, y);
+			if chunks.loaded.contains_key(&chunk) { continue; }
+
+			let id = spawn_chunk(&mut commands, &tilesheet, map, autotile_set, &layer_z, chunk);
+			commands.entity(map_id).add_child(id);
+			chunks.loaded.insert(chunk, id);
+		}
+	}
+}
+
+// Helpers
+// =========================================================================
+
+/// The z of every layer in the map. Repeated layers of one kind stack
+/// within that kind's z range.
+fn layer_z (map : &MapAsset) -> Vec<f32> {
+	let mut kind_counts = HashMap::new();
+
+	map.layers.iter().map(|layer| {
+		let count = kind_counts.entry(layer.kind).or_insert(0);
+		let z = layer.kind.base_z() + *count as f32 * 10.;
+		*count += 1;
+		z
+	}).collect()
+}
+
+/// The first and last chunk (inclusive) seen from `centre`, padded by
+/// `margin` chunks on every side
+fn chunk_range (centre : Vec3, half_view : Vec2, margin : i32) -> (IVec2, IVec2) {
+	let min = translation_to_cell(centre + Vec3::new(-half_view.x, half_view.y, 0.)) - 1;
+	let max = translation_to_cell(centre + Vec3::new(half_view.x, -half_view.y, 0.)) + 1;
+
+	(
+		IVec2::new(min.x.div_euclid(CHUNK_SIZE), min.y.div_euclid(CHUNK_SIZE)) - margin,
+		IVec2::new(max.x.div_euclid(CHUNK_SIZE), max.y.div_euclid(CHUNK_SIZE)) + margin,
+	)
+}
+
+fn tile_at (layer : &MapLayer, cell : IVec2) -> Option<usize> {
+	if cell.x < 0 || cell.y < 0 { return None; }
+	*layer.cells.get(cell.y as usize)?.get(cell.x as usize)?
+}
+
+fn spawn_chunk (
+	commands : &mut Commands,
+	tilesheet : &Tilesheet,
+	map : &MapAsset,
+	autotiles : Option<&AutotileSet>,
+	layer_z : &[f32],
+	chunk : IVec2,
+) -> Entity {
+	let origin = chunk * CHUNK_SIZE;
+	let mut tiles = Vec::new();
+
+	for (layer, &z) in map.layers.iter().zip(layer_z) {
+		for y in origin.y..origin.y + CHUNK_SIZE {
+			let Some(row) = layer.cells.get(y as usize) else { break };
+
+			for x in origin.x..origin.x + CHUNK_SIZE {
+				let Some(Some(tile)) = row.get(x as usize) else { continue };
+				let def = &map.tiles[*tile];
+				let cell = IVec2::new(x, y);
+				let translation = cell_to_translation(cell, z);
+
+				let piece = def.autotile
+					.as_ref()
+					.and_then(|name| autotiles?.0.get(name))
+					.map(|rules| rules.piece(|offset| {
+						tile_at(layer, cell + offset)
+							.is_some_and(|other| map.tiles[other].autotile == def.autotile)
+					}));
+
+				let tile = spawn_tilesheet_sprite(
+					commands,
+					tilesheet,
+					piece.as_ref().map_or(def.index, |piece| piece.index),
+					translation,
+					def.tint,
+				);
+
+				let mut entity = commands.entity(tile);
+				if let Some(piece) = &piece { entity.insert(piece.transform(translation)); }
+
+				// Autotiled tiles animate through their piece's frames, which
+				// keep the piece's shape, and everything else through its own
+				if let Some(animation) = &def.animation {
+					let frames = match piece {
+						Some(piece) => piece.frames,
+						None => animation.frames
+							.iter()
+							.map(|&index| TileFrame { index, ..default() })
+							.collect(),
+					};
+
+					if !frames.is_empty() {
+						entity.insert(AnimatedTile { frames, fps: animation.fps });
+					}
+				}
+				if def.collider { entity.insert(TileCollider); }
+				if def.encounter.is_some() { entity.insert(EncounterSpawner); }
+				if let Some(npc) = &def.npc { entity.insert(npc.clone()); }
+				if !def.tags.is_empty() { entity.insert(TileTags(def.tags.clone())); }
+
+				tiles.push(tile);
+			}
+		}
+	}
+
+	commands
+		.spawn((
+			Name::new(format!("Chunk ({}, {})", chunk.x, chunk.y)),
+			MapChunk,
+			GlobalTransform::default(),
+			Transform::default(),
+			Visibility::default(),
+			ComputedVisibility::default(),
+		))
+		.push_children(&tiles)
+		.id()
+}
+
+#[cfg(test)]
+mod tests {
+	use bevy::ecs::system::CommandQueue;
+	use super::*;
+	use crate::core::tilemap::text::parse_text_map;
+
+	#[test]
+	fn test_map_water_animates () {
+		let map = parse_text_map(&std::fs::read("assets/maps/test.txt").unwrap()).unwrap();
+		let text = std::fs::read_to_string("assets/data/base.autotiles.ron").unwrap();
+		let autotiles : AutotileSet = ron::de::from_str(&text).unwrap();
+
+		let mut world = World::new();
+		let mut queue = CommandQueue::default();
+		let mut commands = Commands::new(&mut queue, &world);
+		let tilesheet = Tilesheet(default());
+		let layer_z = layer_z(&map);
+
+		let chunk_count = (map_size(&map).as_ivec2() + (CHUNK_SIZE - 1)) / CHUNK_SIZE;
+		for y in 0..chunk_count.y {
+			for x in 0..chunk_count.x {
+				spawn_chunk(&mut commands, &tilesheet, &map, Some(&autotiles), &layer_z, IVec2::new(x, y));
+			}
+		}
+		queue.apply(&mut world);
+
+		let water_cells = map.layers
+			.iter()
+			.flat_map(|layer| layer.cells.iter().flatten().flatten())
+			.filter(|&&tile| map.tiles[tile].autotile.as_deref() == Some("water"))
+			.count();
+
+		// Water pieces are drawn from tilesheet indices 253 to 256
+		let mut water = world.query::<(&TextureAtlasSprite, Option<&AnimatedTile>)>();
+		let water : Vec<_> = water
+			.iter(&world)
+			.filter(|(sprite, _)| (253..=256).contains(&sprite.index))
+			.collect();
+
+		assert_eq!(water.len(), water_cells);
+		// Open water as well as shores and corners
+		assert!(water.iter().any(|(sprite, _)| sprite.index == 253));
+		assert!(water.iter().any(|(sprite, _)| sprite.index == 254));
+		assert!(water.iter().all(|(_, animated)| animated.is_some_and(|tile| tile.frames.len() > 1)));
+	}
+}
@@ -0,0 +1,328 @@
+use bevy::prelude::*;
+use crate::core::tilemap::{MapAsset, MapObjectKind, Warp};
+use crate::TILE_SIZE;
+
+// Resources
+// =========================================================================
+
+/// Per-cell gameplay data for the active map, so collision and encounter
+/// checks only have to look at the handful of cells around the player
+/// instead of every tile entity.
+#[derive(Resource, Default)]
+pub struct MapGrid {
+	size : UVec2,
+	cells : Vec<GridCell>,
+	warps : Vec<Warp>,
+	encounters : Vec<String>,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct GridCell {
+	pub collider : bool,
+	encounter : Option<usize>,
+	warp : Option<usize>,
+}
+
+impl MapGrid {
+	pub fn from_map (map : &MapAsset) -> Self {
+		let size = map_size(map);
+		let mut grid = Self {
+			size,
+			cells: vec![GridCell::default(); (size.x * size.y) as usize],
+			warps: Vec::new(),
+			encounters: Vec::new(),
+		};
+
+		for layer in &map.layers {
+			for (y, row) in layer.cells.iter().enumerate() {
+				for (x, tile) in row.iter().enumerate() {
+					let Some(def) = tile.map(|i| &map.tiles[i]) else { continue };
+					let encounter = def.encounter.as_ref().map(|table| grid.encounter_index(table));
+					let Some(cell) = grid.get_mut(IVec2::new(x as i32, y as i32)) else { continue };
+
+					cell.collider |= def.collider;
+					cell.encounter = encounter.or(cell.encounter);
+				}
+			}
+		}
+
+		for object in &map.objects {
+			match &object.kind {
+				MapObjectKind::Npc { .. } => {
+					if let Some(cell) = grid.get_mut(object.cell) {
+						cell.collider = true;
+					}
+				}
+				MapObjectKind::Warp(warp) => {
+					let index = grid.warps.len();
+					grid.warps.push(warp.clone());
+
+					for y in 0..object.size.y as i32 {
+						for x in 0..object.size.x as i32 {
+							if let Some(cell) = grid.get_mut(object.cell + IVec2::new(x, y)) {
+								cell.warp = Some(index);
+							}
+						}
+					}
+				}
+				_ => {}
+			}
+		}
+
+		grid
+	}
+
+	pub fn size (&self) -> UVec2 {
+		self.size
+	}
+
+	pub fn get (&self, cell : IVec2) -> Option<&GridCell> {
+		self.index(cell).map(|i| &self.cells[i])
+	}
+
+	pub fn warp (&self, cell : IVec2) -> Option<&Warp> {
+		self.get(cell)?.warp.map(|i| &self.warps[i])
+	}
+
+	/// The encounter table of the first encounter cell a box centred on
+	/// `translation` overlaps
+	pub fn encounter_overlapping (&self, translation : Vec3, half_size : Vec2) -> Option<&str> {
+		cells_overlapping(translation, half_size)
+			.find_map(|cell| self.get(cell)?.encounter)
+			.map(|i| self.encounters[i].as_str())
+	}
+
+	/// Whether a box centred on `translation` overlaps any cell matching
+	/// `check`. Cells outside the map never match.
+	pub fn any_overlapping (
+		&self,
+		translation : Vec3,
+		half_size : Vec2,
+		check : impl Fn(&GridCell) -> bool,
+	) -> bool {
+		cells_overlapping(translation, half_size)
+			.any(|cell| self.get(cell).is_some_and(&check))
+	}
+
+	fn encounter_index (&mut self, table : &str) -> usize {
+		match self.encounters.iter().position(|name| name == table) {
+			Some(i) => i,
+			None => {
+				self.encounters.push(table.to_string());
+				self.encounters.len() - 1
+			}
+		}
+	}
+
+	fn get_mut (&mut self, cell : IVec2) -> Option<&mut GridCell> {
+		self.index(cell).map(|i| &mut self.cells[i])
+	}
+
+	fn index (&self, cell : IVec2) -> Option<usize> {
+		if cell.x < 0 || cell.y < 0 || cell.x as u32 >= self.size.x || cell.y as u32 >= self.size.y {
+			return None;
+		}
+
+		Some((cell.y as u32 * self.size.x + cell.x as u32) as usize)
+	}
+}
+
+// Utilities
+// =========================================================================
+
+/// The width and height in cells of the largest layer
+pub fn map_size (map : &MapAsset) -> UVec2 {
+	map.layers.iter().fold(UVec2::ZERO, |size, layer| {
+		let width = layer.cells.iter().map(Vec::len).max().unwrap_or(0) as u32;
+		size.max(UVec2::new(width, layer.cells.len() as u32))
+	})
+}
+
+/// The cell whose tile is drawn over the given world position
+pub fn translation_to_cell (translation : Vec3) -> IVec2 {
+	IVec2::new(
+		(translation.x / TILE_SIZE).round() as i32,
+		(-translation.y / TILE_SIZE).round() as i32,
+	)
+}
+
+/// Every cell a box centred on `translation` strictly overlaps
+pub fn cells_overlapping (
+	translation : Vec3,
+	half_size : Vec2,
+) -> impl Iterator<Item = IVec2> {
+	// Tiles are centred on their cell, so a cell overlaps when its centre is
+	// closer than both half sizes combined
+	let reach = half_size + Vec2::splat(TILE_SIZE * 0.5);
+	let x = translation.x / TILE_SIZE;
+	let y = -translation.y / TILE_SIZE;
+	let reach = reach / TILE_SIZE;
+
+	let min = IVec2::new((x - reach.x).floor() as i32 + 1, (y - reach.y).floor() as i32 + 1);
+	let max = IVec2::new((x + reach.x).ceil() as i32 - 1, (y + reach.y).ceil() as i32 - 1);
+
+	(min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::tilemap::{DEFAULT_ENCOUNTERS, LayerKind, MapLayer};
+	use crate::core::tilemap::text::parse_text_map;
+
+	const MAP : &str = "\
+[legend]
+. tile=0
+# tile=1 collider
+~ tile=2 encounter=meadow
+! tile=3 encounter
+
+[layer ground]
+.....
+.~..!
+...
+  .
+
+[layer objects]
+#  #
+      #
+
+[warps]
+1 2 house door
+";
+
+	const HALF : Vec2 = Vec2::splat(0.4);
+
+	fn grid () -> MapGrid {
+		MapGrid::from_map(&parse_text_map(MAP.as_bytes()).expect("Map should parse"))
+	}
+
+	/// The world position of a point measured in cells
+	fn at (x : f32, y : f32) -> Vec3 {
+		Vec3::new(x * TILE_SIZE, -y * TILE_SIZE, 0.)
+	}
+
+	// Size
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn size_covers_the_widest_row_of_any_layer () {
+		assert_eq!(grid().size(), UVec2::new(7, 4));
+	}
+
+	#[test]
+	fn empty_maps_have_no_size () {
+		let map = MapAsset {
+			tiles: Vec::new(),
+			layers: vec![MapLayer::new(LayerKind::Ground)],
+			objects: Vec::new(),
+			warnings: Vec::new(),
+		};
+
+		assert_eq!(map_size(&map), UVec2::ZERO);
+		assert!(MapGrid::from_map(&map).get(IVec2::ZERO).is_none());
+	}
+
+	// Cells
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn layers_are_merged () {
+		let grid = grid();
+
+		assert!(grid.get(IVec2::new(0, 0)).unwrap().collider);
+		assert!(grid.get(IVec2::new(3, 0)).unwrap().collider);
+		assert!(!grid.get(IVec2::new(1, 0)).unwrap().collider);
+		// Cells past the end of a ragged row still exist
+		assert!(grid.get(IVec2::new(6, 1)).unwrap().collider);
+		assert!(!grid.get(IVec2::new(6, 3)).unwrap().collider);
+	}
+
+	#[test]
+	fn cells_outside_the_map_are_none () {
+		let grid = grid();
+
+		for cell in [IVec2::new(-1, 0), IVec2::new(0, -1), IVec2::new(7, 0), IVec2::new(0, 4)] {
+			assert!(grid.get(cell).is_none(), "{cell} should be outside the map");
+			assert!(grid.warp(cell).is_none());
+		}
+	}
+
+	#[test]
+	fn warps_cover_their_cells () {
+		let grid = grid();
+
+		let warp = grid.warp(IVec2::new(1, 2)).unwrap();
+		assert_eq!((warp.map.as_str(), warp.spawn.as_str()), ("house", "door"));
+		assert!(grid.warp(IVec2::new(2, 2)).is_none());
+	}
+
+	// Overlapping
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn boxes_inside_a_cell_only_overlap_that_cell () {
+		let grid = grid();
+		let is_collider = |cell : &GridCell| cell.collider;
+
+		assert!(grid.any_overlapping(at(0., 0.), HALF, is_collider));
+		assert!(!grid.any_overlapping(at(1., 0.), HALF, is_collider));
+		assert!(!grid.any_overlapping(at(1., 1.), HALF, is_collider));
+	}
+
+	#[test]
+	fn boxes_between_cells_overlap_both () {
+		let grid = grid();
+
+		assert!(grid.any_overlapping(at(0.5, 0.), HALF, |cell| cell.collider));
+		assert!(grid.any_overlapping(at(2.5, 0.), HALF, |cell| cell.collider));
+		assert!(grid.any_overlapping(at(3., 0.6), HALF, |cell| cell.collider));
+	}
+
+	#[test]
+	fn touching_a_cell_is_not_overlapping_it () {
+		let grid = grid();
+
+		assert!(!grid.any_overlapping(at(1., 0.), Vec2::splat(0.5), |cell| cell.collider));
+		assert!(grid.any_overlapping(at(1., 0.), Vec2::splat(0.51), |cell| cell.collider));
+	}
+
+	#[test]
+	fn outside_the_map_never_matches () {
+		let grid = grid();
+
+		assert!(!grid.any_overlapping(at(-2., 0.), HALF, |_| true));
+		assert!(!grid.any_overlapping(at(3., 5.), HALF, |_| true));
+		assert!(grid.any_overlapping(at(-0.8, 0.), HALF, |_| true));
+	}
+
+	#[test]
+	fn encounters_name_their_table () {
+		let grid = grid();
+
+		assert_eq!(grid.encounter_overlapping(at(1., 1.), HALF), Some("meadow"));
+		assert_eq!(grid.encounter_overlapping(at(4., 1.), HALF), Some(DEFAULT_ENCOUNTERS));
+		assert_eq!(grid.encounter_overlapping(at(2.5, 1.), HALF), None);
+	}
+
+	// Translations
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn translations_round_to_the_nearest_cell () {
+		assert_eq!(translation_to_cell(at(0., 0.)), IVec2::ZERO);
+		assert_eq!(translation_to_cell(at(2.4, 1.6)), IVec2::new(2, 2));
+		assert_eq!(translation_to_cell(at(-0.6, -1.2)), IVec2::new(-1, -1));
+	}
+
+	#[test]
+	fn halfway_translations_round_away_from_the_origin () {
+		assert_eq!(translation_to_cell(at(0.5, 0.5)), IVec2::new(1, 1));
+		assert_eq!(translation_to_cell(at(-0.5, -0.5)), IVec2::new(-1, -1));
+	}
+
+	#[test]
+	fn translations_ignore_depth () {
+		assert_eq!(translation_to_cell(at(3., 2.) + Vec3::Z * 950.), IVec2::new(3, 2));
+	}
+}
@@ -0,0 +1,468 @@
+pub mod autotile;
+pub mod chunks;
+pub mod grid;
+mod text;
+mod tiled;
+pub mod validate;
+
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
+use std::fmt;
+use std::str::FromStr;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use crate::core::assets::{spawn_tilesheet_sprite, Tilesheet};
+use crate::core::data::AddRonAsset;
+use crate::core::tilemap::autotile::{AutotileSet, load_autotiles};
+use crate::core::tilemap::chunks::{MapChunks, stream_chunks};
+use crate::core::tilemap::grid::MapGrid;
+use crate::{GameState, TILE_SIZE};
+use crate::npc::Npc;
+
+// Plugin
+// =========================================================================
+
+pub struct TilemapPlugin;
+
+impl Plugin for TilemapPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_asset::<MapAsset>()
+			.add_ron_asset::<AutotileSet>(&["autotiles.ron"])
+			.init_asset_loader::<MapLoader>()
+			.init_asset_loader::<tiled::TiledMapLoader>()
+			.register_type::<TileTags>()
+			.register_type::<SpawnPoint>()
+			.register_type::<MapTrigger>()
+			.register_type::<Warp>()
+			.add_event::<WarpEvent>()
+			.add_event::<TeleportEvent>()
+			.add_startup_system(load_autotiles)
+			.add_system_set(
+				SystemSet::on_update(GameState::Overworld)
+					.with_system(stream_chunks)
+					.with_system(animate_tiles)
+			)
+		;
+	}
+}
+
+// Assets
+// =========================================================================
+
+/// A loaded map, independent of the file format it came from
+#[derive(TypeUuid, Debug, Default)]
+#[uuid = "ef7d309f-6774-49aa-9127-4404ed6f07a0"]
+pub struct MapAsset {
+	/// Every distinct tile used by the map. Layer cells index into this.
+	pub tiles : Vec<TileDef>,
+	pub layers : Vec<MapLayer>,
+	pub objects : Vec<MapObject>,
+	/// Problems found while parsing that didn't stop the map from loading
+	pub warnings : Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct MapLayer {
+	pub kind : LayerKind,
+	pub cells : Vec<Vec<Option<usize>>>,
+}
+
+impl MapLayer {
+	pub fn new (kind : LayerKind) -> Self {
+		Self { kind, cells: Vec::new() }
+	}
+}
+
+/// Where a layer sits in the draw order. Each kind gets its own z range,
+/// with `Overhead` drawn above the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LayerKind {
+	Ground,
+	Decoration,
+	Objects,
+	Overhead,
+}
+
+impl LayerKind {
+	pub fn base_z (&self) -> f32 {
+		match self {
+			LayerKind::Ground => 100.,
+			LayerKind::Decoration => 200.,
+			LayerKind::Objects => 300.,
+			LayerKind::Overhead => 950.,
+		}
+	}
+}
+
+impl FromStr for LayerKind {
+	type Err = ();
+
+	fn from_str(s : &str) -> Result<Self, Self::Err> {
+		match s {
+			"ground" => Ok(LayerKind::Ground),
+			"decoration" => Ok(LayerKind::Decoration),
+			"objects" => Ok(LayerKind::Objects),
+			"overhead" => Ok(LayerKind::Overhead),
+			_ => Err(()),
+		}
+	}
+}
+
+/// What a single map cell turns into when the map is spawned
+#[derive(Debug, Clone, Default)]
+pub struct TileDef {
+	pub index : usize,
+	pub tint : Option<Color>,
+	pub collider : bool,
+	/// The encounter table battles on this tile are rolled from
+	pub encounter : Option<String>,
+	pub npc : Option<Npc>,
+	pub tags : Vec<String>,
+	/// The autotile rule that picks this tile's index from its neighbours
+	pub autotile : Option<String>,
+	pub animation : Option<TileAnimation>,
+}
+
+/// Tilesheet indices a tile cycles through, and how many it shows a second.
+/// Autotiled tiles have no frames of their own and cycle through their
+/// piece's instead, see [`autotile::AutotilePiece::frames`].
+#[derive(Debug, Clone)]
+pub struct TileAnimation {
+	pub frames : Vec<usize>,
+	pub fps : f32,
+}
+
+impl TileAnimation {
+	pub const DEFAULT_FPS : f32 = 4.;
+
+	/// Builds an animation from a comma separated list of tile indices
+	pub fn parse (frames : &str, fps : Option<f32>) -> Result<Self, String> {
+		let frames = frames
+			.split(',')
+			.map(|frame| frame.trim().parse().map_err(|_| format!("invalid frame '{frame}'")))
+			.collect::<Result<Vec<usize>, _>>()?;
+
+		Ok(Self { frames, fps: Self::check_fps(fps)? })
+	}
+
+	/// An animation for an autotiled tile, which takes its frames from
+	/// whichever autotile piece it's drawn with
+	pub fn autotiled (fps : Option<f32>) -> Result<Self, String> {
+		Ok(Self { frames: Vec::new(), fps: Self::check_fps(fps)? })
+	}
+
+	fn check_fps (fps : Option<f32>) -> Result<f32, String> {
+		let fps = fps.unwrap_or(Self::DEFAULT_FPS);
+		if fps <= 0. {
+			return Err(format!("fps must be above 0 but was {fps}"));
+		}
+
+		Ok(fps)
+	}
+}
+
+/// Something placed on the map that isn't part of the tile grid
+#[derive(Debug, Clone)]
+pub struct MapObject {
+	pub name : String,
+	pub kind : MapObjectKind,
+	/// The top-left cell the object covers
+	pub cell : IVec2,
+	pub size : UVec2,
+}
+
+#[derive(Debug, Clone)]
+pub enum MapObjectKind {
+	Npc { npc : Npc, tile : usize },
+	Spawn,
+	Trigger { properties : HashMap<String, String> },
+	Warp (Warp),
+}
+
+#[derive(Default)]
+pub struct MapLoader;
+
+impl AssetLoader for MapLoader {
+	fn load<'a>(
+		&'a self,
+		bytes : &'a [u8],
+		load_context : &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let map = text::parse_text_map(bytes)?;
+			for warning in &map.warnings {
+				warn!("{}: {warning}", load_context.path().display());
+			}
+
+			load_context.set_default_asset(LoadedAsset::new(map));
+			Ok(())
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["txt"]
+	}
+}
+
+#[derive(Debug)]
+pub enum MapError {
+	InvalidUtf8,
+	Empty,
+	Syntax { line : usize, message : String },
+	Invalid (String),
+}
+
+impl fmt::Display for MapError {
+	fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MapError::InvalidUtf8 => write!(f, "map file is not valid UTF-8"),
+			MapError::Empty => write!(f, "map file contains no tiles"),
+			MapError::Syntax { line, message } => write!(f, "line {line}: {message}"),
+			MapError::Invalid(message) => write!(f, "{message}"),
+		}
+	}
+}
+
+impl std::error::Error for MapError {}
+
+// Events
+// =========================================================================
+
+/// Sent when a warp should happen: the current map is swapped for the
+/// warp's map and the player moved to its spawn point
+pub struct WarpEvent (pub Warp);
+
+/// Sent to move the player to a spot on a map, swapping the current map
+/// for it first if it isn't already showing
+pub struct TeleportEvent {
+	pub map : Handle<MapAsset>,
+	pub position : Vec3,
+}
+
+// Resources
+// =========================================================================
+
+/// The map the overworld should currently be showing
+#[derive(Resource)]
+pub struct ActiveMap (pub Handle<MapAsset>);
+
+// Components
+// =========================================================================
+
+#[derive(Component)]
+pub struct Map;
+
+#[derive(Component)]
+pub struct TileCollider;
+
+#[derive(Component)]
+pub struct EncounterSpawner;
+
+/// One frame of a tile animation: a tilesheet index, optionally mirrored and
+/// then turned clockwise in quarter turns. The orientation replaces the
+/// tile's own, so a frame can reflect an autotile piece across a diagonal
+/// without changing its shape.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TileFrame {
+	pub index : usize,
+	#[serde(default)]
+	pub flip_x : bool,
+	#[serde(default)]
+	pub flip_y : bool,
+	#[serde(default)]
+	pub turns : u8,
+}
+
+impl TileFrame {
+	/// The transform a tile at `translation` needs to show this frame
+	pub fn transform (&self, translation : Vec3) -> Transform {
+		Transform {
+			translation,
+			rotation: Quat::from_rotation_z(-FRAC_PI_2 * self.turns as f32),
+			scale: Vec3::new(
+				if self.flip_x { -1. } else { 1. },
+				if self.flip_y { -1. } else { 1. },
+				1.,
+			),
+		}
+	}
+}
+
+/// Cycles a tile sprite through its animation frames. Every tile with the
+/// same animation shows the same frame, however long it has been spawned.
+#[derive(Component)]
+pub struct AnimatedTile {
+	pub frames : Vec<TileFrame>,
+	pub fps : f32,
+}
+
+/// Free-form tags given to a tile by the map legend
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct TileTags (pub Vec<String>);
+
+/// The encounter table used by tiles that are marked as encounters without
+/// naming a table
+pub const DEFAULT_ENCOUNTERS : &str = "default";
+
+/// The spawn point the player is placed at when a map is entered without
+/// asking for a specific one
+pub const DEFAULT_SPAWN : &str = "start";
+
+/// The spawn point on the starting map the party wakes up at when they're
+/// defeated before saving at a healer
+pub const RESPAWN : &str = "respawn";
+
+/// A named spot on the map the player can be placed at
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct SpawnPoint (pub String);
+
+/// An area of the map that something can react to the player entering
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct MapTrigger {
+	pub name : String,
+	pub size : Vec2,
+	pub properties : bevy::utils::HashMap<String, String>,
+}
+
+/// A cell that sends the player to a spawn point on another map
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct Warp {
+	pub map : String,
+	pub spawn : String,
+}
+
+// Systems
+// =========================================================================
+
+fn animate_tiles (
+	time : Res<Time>,
+	mut query : Query<(&AnimatedTile, &mut TextureAtlasSprite, &mut Transform)>,
+) {
+	let elapsed = time.elapsed_seconds();
+
+	for (animation, mut sprite, mut transform) in &mut query {
+		let frame = animation.frames[(elapsed * animation.fps) as usize % animation.frames.len()];
+		let oriented = frame.transform(transform.translation);
+
+		if sprite.index != frame.index { sprite.index = frame.index; }
+		if *transform != oriented { *transform = oriented; }
+	}
+}
+
+// Utilities
+// =========================================================================
+
+/// Loads a map from `assets/maps`. Names without an extension are text
+/// maps, Tiled maps are loaded by giving their `.tmj` or `.tmx` extension.
+pub fn load_map (
+	name : &str,
+	assets : &AssetServer,
+) -> Handle<MapAsset> {
+	if name.contains('.') {
+		assets.load(format!("maps/{name}"))
+	} else {
+		assets.load(format!("maps/{name}.txt"))
+	}
+}
+
+/// Spawns the root entity for a map and its objects, and makes the map's
+/// grid the active `MapGrid`. Tiles are streamed in as chunks around the
+/// camera by `stream_chunks`.
+pub fn create_simple_map (
+	map : &MapAsset,
+	commands : &mut Commands,
+	tilesheet : &Res<Tilesheet>,
+) -> Entity {
+	let objects : Vec<Entity> = map.objects
+		.iter()
+		.map(|object| spawn_map_object(object, commands, tilesheet))
+		.collect();
+
+	commands.insert_resource(MapGrid::from_map(map));
+
+	commands
+		.spawn((
+			Name::new("Map"),
+			GlobalTransform::default(),
+			Transform::default(),
+			Visibility::default(),
+			ComputedVisibility::default(),
+			Map,
+			MapChunks::default(),
+		))
+		.push_children(&objects)
+		.id()
+}
+
+/// The world position of the centre of a map cell
+pub fn cell_to_translation (cell : IVec2, z : f32) -> Vec3 {
+	Vec3::new(cell.x as f32 * TILE_SIZE, -(cell.y as f32) * TILE_SIZE, z)
+}
+
+// Helpers
+// =========================================================================
+
+fn spawn_map_object (
+	object : &MapObject,
+	commands : &mut Commands,
+	tilesheet : &Res<Tilesheet>,
+) -> Entity {
+	// Objects are anchored on their top-left cell, so shift multi-cell
+	// objects to the middle of the area they cover
+	let offset = (object.size.as_vec2() - Vec2::ONE) * Vec2::new(0.5, -0.5) * TILE_SIZE;
+	let translation = cell_to_translation(object.cell, LayerKind::Objects.base_z())
+		+ offset.extend(0.);
+
+	let name = Name::new(object.name.clone());
+
+	match &object.kind {
+		MapObjectKind::Npc { npc, tile } => {
+			let id = spawn_tilesheet_sprite(
+				commands,
+				tilesheet,
+				*tile,
+				translation,
+				None,
+			);
+
+			commands.entity(id).insert((name, npc.clone(), TileCollider));
+			id
+		}
+		MapObjectKind::Spawn => {
+			commands.spawn((
+				name,
+				SpawnPoint(object.name.clone()),
+				TransformBundle::from_transform(Transform::from_translation(translation)),
+			)).id()
+		}
+		MapObjectKind::Trigger { properties } => {
+			commands.spawn((
+				name,
+				MapTrigger {
+					name: object.name.clone(),
+					size: object.size.as_vec2() * TILE_SIZE,
+					properties: properties
+						.iter()
+						.map(|(key, value)| (key.clone(), value.clone()))
+						.collect(),
+				},
+				TransformBundle::from_transform(Transform::from_translation(translation)),
+			)).id()
+		}
+		MapObjectKind::Warp(warp) => {
+			commands.spawn((
+				name,
+				warp.clone(),
+				TransformBundle::from_transform(Transform::from_translation(translation)),
+			)).id()
+		}
+	}
+}
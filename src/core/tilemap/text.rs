@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use crate::core::tilemap::{DEFAULT_ENCOUNTERS, LayerKind, MapAsset, MapError, MapLayer, MapObject, MapObjectKind, TileAnimation, TileDef, Warp};
+
+// Parsing
+// =========================================================================
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+	Legend,
+	Layer,
+	Spawns,
+	Warps,
+}
+
+/// Parses the plain text map format.
+///
+/// A map file is split into a `[legend]` section and one or more
+/// `[layer <kind>]` grids, where kind is `ground`, `decoration`, `objects` or
+/// `overhead`. Layers are stacked in that order, and layers of the same kind
+/// in the order they appear. `[map]` is shorthand for `[layer ground]`, and a
+/// file without any section headers is read as a single ground layer.
+///
+/// `[spawns]` lists named spawn points as `name x y`, with the player
+/// starting at the one named `start`. A party defeated before saving wakes
+/// up at `respawn` on the starting map. `[warps]` lists cells that send the
+/// player to a spawn point on another map as `x y map spawn`.
+///
+/// Tiles animate through a comma separated list of `frames`, shown `fps` a
+/// second. Autotiled tiles are marked `animated` instead and cycle through
+/// the frames of whichever autotile piece they're drawn with.
+///
+/// Tiles marked with `encounter=<table>` start battles rolled from that
+/// encounter table, or the `default` table for a bare `encounter`.
+///
+/// Spaces in a layer are empty cells. Lines starting with `//` outside of
+/// layers are comments.
+///
+/// ```text
+/// [legend]
+/// . tile=0
+/// # tile=169 collider autotile=wall
+/// ~ tile=5 tint=6ED57E encounter=meadow frames=5,7 fps=2
+/// @ tile=80 npc=healer collider tag=town
+///
+/// [layer ground]
+/// .....
+/// .....
+///
+/// [layer objects]
+/// #####
+/// #~@~#
+///
+/// [spawns]
+/// start 1 1
+///
+/// [warps]
+/// 4 1 house door
+/// ```
+pub fn parse_text_map (bytes : &[u8]) -> Result<MapAsset, MapError> {
+	let text = std::str::from_utf8(bytes).map_err(|_| MapError::InvalidUtf8)?;
+
+	let mut section = Section::Layer;
+	let mut legend = HashMap::new();
+	let mut layers = vec![MapLayer::new(LayerKind::Ground)];
+	let mut rows = vec![Vec::new()];
+	let mut objects = Vec::new();
+
+	for (i, line) in text.lines().enumerate() {
+		let line_no = i + 1;
+		let line = line.trim_end_matches('\r');
+
+		if let Some(name) = section_header(line) {
+			let syntax_error = || MapError::Syntax {
+				line: line_no,
+				message: format!("unknown section [{name}]"),
+			};
+
+			section = match name.split_once(' ') {
+				None if name == "legend" => Section::Legend,
+				None if name == "spawns" => Section::Spawns,
+				None if name == "warps" => Section::Warps,
+				None if name == "map" => {
+					layers.push(MapLayer::new(LayerKind::Ground));
+					rows.push(Vec::new());
+					Section::Layer
+				}
+				Some(("layer", kind)) => {
+					let kind = kind.trim().parse().map_err(|_| syntax_error())?;
+					layers.push(MapLayer::new(kind));
+					rows.push(Vec::new());
+					Section::Layer
+				}
+				_ => return Err(syntax_error()),
+			};
+			continue;
+		}
+
+		let is_blank = line.trim().is_empty() || line.starts_with("//");
+
+		match section {
+			Section::Legend => {
+				if is_blank { continue; }
+
+				let (char, def) = parse_legend_entry(line)
+					.map_err(|message| MapError::Syntax { line: line_no, message })?;
+
+				if legend.insert(char, def).is_some() {
+					return Err(MapError::Syntax {
+						line: line_no,
+						message: format!("'{char}' is defined twice"),
+					});
+				}
+			}
+			Section::Layer => {
+				let layer_rows = rows.last_mut().expect("Map always has a layer");
+				layer_rows.push(line.chars().collect::<Vec<_>>());
+			}
+			Section::Spawns => {
+				if is_blank { continue; }
+
+				objects.push(
+					parse_spawn(line).map_err(|message| MapError::Syntax { line: line_no, message })?
+				);
+			}
+			Section::Warps => {
+				if is_blank { continue; }
+
+				objects.push(
+					parse_warp(line).map_err(|message| MapError::Syntax { line: line_no, message })?
+				);
+			}
+		}
+	}
+
+	// Give each legend entry a slot in the map's tile list
+	let mut tiles = Vec::new();
+	let mut palette = HashMap::new();
+	for (char, def) in legend {
+		palette.insert(char, tiles.len());
+		tiles.push(def);
+	}
+
+	let mut warnings = Vec::new();
+
+	for (layer, rows) in layers.iter_mut().zip(rows.iter_mut()) {
+		while rows.first().is_some_and(|row| row.is_empty()) { rows.remove(0); }
+		while rows.last().is_some_and(|row| row.is_empty()) { rows.pop(); }
+
+		for (y, row) in rows.iter().enumerate() {
+			layer.cells.push(row.iter().enumerate().map(|(x, char)| {
+				if *char == ' ' { return None; }
+
+				let index = palette.get(char).copied();
+				if index.is_none() {
+					warnings.push(format!("character '{char}' at {x}, {y} is not in the legend"));
+				}
+				index
+			}).collect());
+		}
+	}
+
+	// The implicit first layer only holds anything for header-less files
+	layers.retain(|layer| !layer.cells.is_empty());
+	layers.sort_by_key(|layer| layer.kind);
+
+	if layers.is_empty() {
+		return Err(MapError::Empty);
+	}
+
+	Ok(MapAsset { tiles, layers, objects, warnings })
+}
+
+// Helpers
+// =========================================================================
+
+fn section_header (line : &str) -> Option<&str> {
+	let name = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+
+	if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphabetic() || c == ' ') {
+		Some(name)
+	} else {
+		None
+	}
+}
+
+fn parse_spawn (line : &str) -> Result<MapObject, String> {
+	let [name, x, y] = split_fields(line)?;
+
+	Ok(MapObject {
+		name: name.to_string(),
+		kind: MapObjectKind::Spawn,
+		cell: parse_cell(x, y)?,
+		size: UVec2::ONE,
+	})
+}
+
+fn parse_warp (line : &str) -> Result<MapObject, String> {
+	let [x, y, map, spawn] = split_fields(line)?;
+
+	Ok(MapObject {
+		name: format!("Warp to {map}"),
+		kind: MapObjectKind::Warp(Warp {
+			map: map.to_string(),
+			spawn: spawn.to_string(),
+		}),
+		cell: parse_cell(x, y)?,
+		size: UVec2::ONE,
+	})
+}
+
+fn split_fields<const N : usize> (line : &str) -> Result<[&str; N], String> {
+	let fields : Vec<&str> = line.split_whitespace().collect();
+
+	fields
+		.try_into()
+		.map_err(|fields : Vec<&str>| format!("expected {N} values but found {}", fields.len()))
+}
+
+fn parse_cell (x : &str, y : &str) -> Result<IVec2, String> {
+	let parse = |value : &str| value
+		.parse::<i32>()
+		.map_err(|_| format!("invalid coordinate '{value}'"));
+
+	Ok(IVec2::new(parse(x)?, parse(y)?))
+}
+
+fn parse_legend_entry (line : &str) -> Result<(char, TileDef), String> {
+	let mut chars = line.chars();
+	let char = chars.next().ok_or("missing legend character")?;
+	let props = chars.as_str();
+
+	if !props.starts_with(char::is_whitespace) {
+		return Err(format!("expected a space after '{char}'"));
+	}
+
+	let mut def = TileDef::default();
+	let mut has_tile = false;
+	let mut frames = None;
+	let mut animated = false;
+	let mut fps = None;
+
+	for prop in props.split_whitespace() {
+		let (key, value) = match prop.split_once('=') {
+			Some((key, value)) => (key, Some(value)),
+			None => (prop, None),
+		};
+
+		match (key, value) {
+			("tile", Some(value)) => {
+				def.index = value
+					.parse()
+					.map_err(|_| format!("invalid tile index '{value}'"))?;
+				has_tile = true;
+			}
+			("tint", Some(value)) => {
+				def.tint = Some(
+					Color::hex(value).map_err(|_| format!("invalid tint '{value}'"))?
+				);
+			}
+			("npc", Some(value)) => {
+				def.npc = Some(value.parse()?);
+			}
+			("tag", Some(value)) => def.tags.push(value.to_string()),
+			("autotile", Some(value)) => def.autotile = Some(value.to_string()),
+			("frames", Some(value)) => frames = Some(value),
+			("animated", None) => animated = true,
+			("fps", Some(value)) => {
+				fps = Some(value.parse().map_err(|_| format!("invalid fps '{value}'"))?);
+			}
+			("collider", None) => def.collider = true,
+			("encounter", None) => def.encounter = Some(DEFAULT_ENCOUNTERS.to_string()),
+			("encounter", Some(value)) => def.encounter = Some(value.to_string()),
+			_ => return Err(format!("unknown property '{prop}'")),
+		}
+	}
+
+	if !has_tile {
+		return Err(format!("'{char}' is missing a tile index"));
+	}
+
+	// Autotiled tiles take their frames from their pieces, so they're only
+	// marked as animated, and everything else lists its own frames
+	def.animation = match (frames, animated) {
+		(Some(_), true) => return Err(format!("'{char}' has both frames and animated")),
+		(Some(_), false) if def.autotile.is_some() => {
+			return Err(format!("'{char}' is autotiled, so its frames come from its autotile pieces"));
+		}
+		(Some(frames), false) => Some(TileAnimation::parse(frames, fps)?),
+		(None, true) if def.autotile.is_none() => {
+			return Err(format!("'{char}' is animated but not autotiled, so has no frames"));
+		}
+		(None, true) => Some(TileAnimation::autotiled(fps)?),
+		(None, false) if fps.is_some() => return Err(format!("'{char}' has an fps but no frames")),
+		(None, false) => None,
+	};
+
+	Ok((char, def))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::npc::Npc;
+
+	fn parse (text : &str) -> MapAsset {
+		parse_text_map(text.as_bytes()).expect("Map should parse")
+	}
+
+	fn syntax_error (text : &str) -> (usize, String) {
+		match parse_text_map(text.as_bytes()) {
+			Err(MapError::Syntax { line, message }) => (line, message),
+			other => panic!("expected a syntax error, got {other:?}"),
+		}
+	}
+
+	fn legend (entry : &str) -> TileDef {
+		parse_legend_entry(entry).expect("Legend entry should parse").1
+	}
+
+	fn legend_error (entry : &str) -> String {
+		parse_legend_entry(entry).expect_err("Legend entry should not parse")
+	}
+
+	// Legend
+	// ---------------------------------------------------------------------
+
+	#[test]
+	fn legend_reads_tile_index () {
+		let (char, def) = parse_legend_entry("# tile=169").unwrap();
+
+		assert_eq!(char, '#');
+		assert_eq!(def.index, 169);
+		assert!(!def.collider);
+		assert!(def.tint.is_none());
+	}
+
+	#[test]
+	fn legend_needs_a_valid_tile_index () {
+		assert_eq!(legend_error("# collider"), "'#' is missing a tile index");
+		assert_eq!(legend_error("# tile=wall"), "invalid tile index 'wall'");
+		assert_eq!(legend_error("# tile=-1"), "invalid tile index '-1'");
+	}
+
+	#[test]
+	fn legend_needs_a_space_after_the_char () {
+		assert_eq!(legend_error("#tile=1"), "expected a space after '#'");
+	}
+
+	#[test]
+	fn legend_reads_tint () {
+		assert_eq!(legend("~ tile=5 tint=6ED57E").tint, Some(Color::hex("6ED57E").unwrap()));
+		assert_eq!(legend_error("~ tile=5 tint=green"), "invalid tint 'green'");
+	}
+
+	#[test]
+	fn legend_reads_npc () {
+		assert!(matches!(legend("@ tile=80 npc=healer").npc, Some(Npc::Healer)));
+		assert!(matches!(
+			legend("@ tile=80 npc=recruit:knight").npc,
+			Some(Npc::Recruit(id)) if id == "knight",
+		));
+		assert_eq!(legend_error("@ tile=80 npc=shopkeeper"), "unknown npc 'shopkeeper'");
+		assert_eq!(legend_error("@ tile=80 npc=recruit:"), "unknown npc 'recruit:'");
+	}
+
+	#[test]
+	fn legend_reads_flags_and_tags () {
+		let def = legend("# tile=169 collider tag=town tag=wall");
+
+		assert!(def.collider);
+		assert_eq!(def.tags, ["town", "wall"]);
+		assert_eq!(legend_error("# tile=169 collider=yes"), "unknown property 'collider=yes'");
+	}
+
+	#[test]
+	fn legend_reads_encounter_table () {
+		assert_eq!(legend("~ tile=5 encounter").encounter.as_deref(), Some(DEFAULT_ENCOUNTERS));
+		assert_eq!(legend("~ tile=5 encounter=meadow").encounter.as_deref(), Some("meadow"));
+		assert_eq!(legend("~ tile=5").encounter, None);
+	}
+
+	#[test]
+	fn legend_reads_autotile () {
+		assert_eq!(legend("# tile=169 autotile=wall").autotile.as_deref(), Some("wall"));
+		assert_eq!(legend_error("# tile=169 autotile"), "unknown property 'autotile'");
+	}
+
+	#[test]
+	fn legend_reads_animation () {
+		let animation = legend("w tile=253 frames=253,254 fps=2").animation.unwrap();
+		assert_eq!(animation.frames, [253, 254]);
+		assert_eq!(animation.fps, 2.);
+
+		let animation = legend("w tile=253 frames=253,254").animation.unwrap();
+		assert_eq!(animation.fps, TileAnimation::DEFAULT_FPS);
+	}
+
+	#[test]
+	fn legend_rejects_bad_animation () {
+		assert_eq!(legend_error("w tile=253 fps=2"), "'w' has an fps but no frames");
+		assert_eq!(legend_error("w tile=253 frames=253,x"), "invalid frame 'x'");
+		assert_eq!(legend_error("w tile=253 frames=253 fps=fast"), "invalid fps 'fast'");
+		assert_eq!(legend_error("w tile=253 frames=253 fps=0"), "fps must be above 0 but was 0");
+	}
+
+	#[test]
+	fn legend_reads_autotiled_animation () {
+		let animation = legend("w tile=253 autotile=water animated fps=1.5").animation.unwrap();
+		assert!(animation.frames.is_empty());
+		assert_eq!(animation.fps, 1.5);
+	}
+
+	#[test]
+	fn legend_rejects_frames_on_autotiled_tiles () {
+		assert_eq!(
+			legend_error("w tile=253 autotile=water frames=253"),
+			"'w' is autotiled, so its frames come from its autotile pieces",
+		);
+		assert_eq!(
+			legend_error("w tile=253 animated"),
+			"'w' is animated but not autotiled, so has no frames",
+		);
+		assert_eq!(
+			legend_error("w tile=253 autotile=water animated frames=253"),
+			"'w' has both frames and animated",
+		);
+	}
+
+	#[test]
+	fn legend_rejects_unknown_property () {
+		assert_eq!(legend_error("# tile=1 solid"), "unknown property 'solid'");
+	}
+
+	#[test]
+	fn legend_char_defined_twice_is_an_error () {
+		let (line, message) = syntax_error("[legend]\n# tile=1\n# tile=2\n[map]\n#");
+
+		assert_eq!(line, 3);
+		assert_eq!(message, "'#' is defined twice");
+	}
+
+	#[test]
+	fn legend_errors_report_their_line () {
+		let (line, message) = syntax_error("[legend]\n// walls\n# tile=wall\n[map]\n#");
+
+		assert_eq!(line, 3);
+		assert_eq!(message, "invalid tile index 'wall'");
+	}
+
+	// Layers
+	// ---------------------------------------------------------------------
+
+	#[test]
+	fn file_without_sections_is_one_ground_layer () {
+		let map = parse("..\n..");
+
+		assert_eq!(map.layers.len(), 1);
+		assert_eq!(map.layers[0].kind, LayerKind::Ground);
+		assert_eq!(map.layers[0].cells.len(), 2);
+		assert_eq!(map.warnings.len(), 4);
+	}
+
+	#[test]
+	fn layers_are_sorted_by_kind () {
+		let map = parse("\
+[legend]
+. tile=0
+# tile=1
+
+[layer overhead]
+#
+
+[layer objects]
+#
+
+[map]
+.
+
+[layer decoration]
+.
+");
+
+		let kinds : Vec<LayerKind> = map.layers.iter().map(|layer| layer.kind).collect();
+		assert_eq!(kinds, [LayerKind::Ground, LayerKind::Decoration, LayerKind::Objects, LayerKind::Overhead]);
+	}
+
+	#[test]
+	fn layer_cells_index_the_legend () {
+		let map = parse("[legend]\n. tile=0\n# tile=169 collider\n[layer ground]\n.# \n#.");
+		let tile = |x : usize, y : usize| map.layers[0].cells[y][x].map(|index| map.tiles[index].index);
+
+		assert_eq!(tile(0, 0), Some(0));
+		assert_eq!(tile(1, 0), Some(169));
+		assert_eq!(tile(2, 0), None);
+		assert_eq!(tile(0, 1), Some(169));
+		assert!(map.warnings.is_empty());
+	}
+
+	#[test]
+	fn unknown_layer_kind_is_an_error () {
+		assert_eq!(syntax_error("[layer sky]\n."), (1, "unknown section [layer sky]".to_string()));
+	}
+
+	#[test]
+	fn unknown_section_is_an_error () {
+		assert_eq!(syntax_error(". tile=0\n[npcs]"), (2, "unknown section [npcs]".to_string()));
+	}
+
+	#[test]
+	fn unknown_char_is_a_warning () {
+		let map = parse("[legend]\n. tile=0\n[map]\n.x");
+
+		assert_eq!(map.layers[0].cells[0], [Some(0), None]);
+		assert_eq!(map.warnings, ["character 'x' at 1, 0 is not in the legend"]);
+	}
+
+	#[test]
+	fn map_without_tiles_is_empty () {
+		assert!(matches!(parse_text_map(b"[legend]\n. tile=0\n"), Err(MapError::Empty)));
+		assert!(matches!(parse_text_map(&[0xFF, 0xFE]), Err(MapError::InvalidUtf8)));
+	}
+
+	// Spawns and warps
+	// ---------------------------------------------------------------------
+
+	#[test]
+	fn spawns_are_read () {
+		let map = parse("[legend]\n. tile=0\n[map]\n..\n[spawns]\n// where the game starts\nstart 1 0\n");
+
+		assert_eq!(map.objects.len(), 1);
+		assert_eq!(map.objects[0].name, "start");
+		assert_eq!(map.objects[0].cell, IVec2::new(1, 0));
+		assert!(matches!(map.objects[0].kind, MapObjectKind::Spawn));
+	}
+
+	#[test]
+	fn bad_spawns_are_errors () {
+		assert_eq!(
+			syntax_error("[map]\n.\n[spawns]\nstart 1"),
+			(4, "expected 3 values but found 2".to_string()),
+		);
+		assert_eq!(
+			syntax_error("[map]\n.\n[spawns]\nstart 1 y"),
+			(4, "invalid coordinate 'y'".to_string()),
+		);
+	}
+
+	#[test]
+	fn warps_are_read () {
+		let map = parse("[legend]\n. tile=0\n[map]\n..\n[warps]\n1 0 house door\n");
+
+		assert_eq!(map.objects[0].cell, IVec2::new(1, 0));
+		match &map.objects[0].kind {
+			MapObjectKind::Warp(warp) => {
+				assert_eq!(warp.map, "house");
+				assert_eq!(warp.spawn, "door");
+			}
+			kind => panic!("expected a warp, got {kind:?}"),
+		}
+	}
+
+	#[test]
+	fn bad_warps_are_errors () {
+		assert_eq!(
+			syntax_error("[map]\n.\n[warps]\n1 0 house"),
+			(4, "expected 4 values but found 3".to_string()),
+		);
+		assert_eq!(
+			syntax_error("[map]\n.\n[warps]\n1.5 0 house door"),
+			(4, "invalid coordinate '1.5'".to_string()),
+		);
+	}
+}
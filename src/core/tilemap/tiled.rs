@@ -0,0 +1,1055 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use crate::core::assets::TILESHEET_COLUMNS;
+use crate::core::tilemap::{DEFAULT_ENCOUNTERS, LayerKind, MapAsset, MapError, MapLayer, MapObject, MapObjectKind, TileAnimation, TileDef, Warp};
+
+/// The top bits of a Tiled GID store flip and rotation flags
+const GID_FLAGS : u32 = 0xF000_0000;
+
+/// The only tileset image maps can use, since every tile is drawn from the
+/// `Tilesheet` atlas
+const TILESHEET_IMAGE : &str = "tilesheet.png";
+
+// Loader
+// =========================================================================
+
+/// Imports maps made in the [Tiled](https://www.mapeditor.org/) editor, in
+/// either the JSON (`.tmj`) or XML (`.tmx`) format.
+///
+/// Tile layers are named after the [`LayerKind`] they should be drawn as (or
+/// given a `kind` property), and can set `collider` / `encounter` properties
+/// to apply them to every tile on the layer. `encounter` names an encounter
+/// table, or is `true` for the default one.
+///
+/// Tileset tiles can set `collider`, `encounter`, `npc`, `tint`, `autotile`,
+/// a comma separated `tags` property and comma separated animation `frames`
+/// with an `fps`. Autotiled tiles set `animated` instead of `frames`.
+///
+/// Objects are read by their class: `npc`, which has to be a tile object,
+/// `spawn`, `trigger` or `warp`, which needs `map` and `spawn` properties.
+#[derive(Default)]
+pub struct TiledMapLoader;
+
+impl AssetLoader for TiledMapLoader {
+	fn load<'a>(
+		&'a self,
+		bytes : &'a [u8],
+		load_context : &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+		Box::pin(async move {
+			let path = load_context.path().to_path_buf();
+			let mut map = parse_tiled_map(&path, bytes)?;
+
+			for tileset in &mut map.tilesets {
+				let Some(tileset_path) = external_tileset_path(&path, tileset) else { continue };
+				let bytes = load_context.asset_io().load_path(&tileset_path).await?;
+				read_external_tileset(tileset, &tileset_path, &bytes)?;
+			}
+
+			let map = build_map(map)?;
+			for warning in &map.warnings {
+				warn!("{}: {warning}", path.display());
+			}
+
+			load_context.set_default_asset(LoadedAsset::new(map));
+			Ok(())
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["tmj", "tmx"]
+	}
+}
+
+/// Reads a Tiled map straight from disk rather than through the asset
+/// server, for tools that run without the rest of the game
+pub fn read_tiled_map (path : &Path) -> Result<MapAsset, MapError> {
+	let read = |path : &Path| std::fs::read(path)
+		.map_err(|e| MapError::Invalid(format!("could not read {}: {e}", path.display())));
+
+	let mut map = parse_tiled_map(path, &read(path)?)?;
+
+	for tileset in &mut map.tilesets {
+		let Some(tileset_path) = external_tileset_path(path, tileset) else { continue };
+		read_external_tileset(tileset, &tileset_path, &read(&tileset_path)?)?;
+	}
+
+	build_map(map)
+}
+
+fn parse_tiled_map (path : &Path, bytes : &[u8]) -> Result<TiledMap, MapError> {
+	if is_xml(path) { parse_tmx(bytes) } else { parse_tmj(bytes) }
+}
+
+/// Where to find a tileset that is saved in its own file. External
+/// tilesets are relative to the map file.
+fn external_tileset_path (map_path : &Path, tileset : &mut TiledTileset) -> Option<PathBuf> {
+	let source = tileset.source.take()?;
+	Some(map_path.parent().unwrap_or(Path::new("")).join(source))
+}
+
+fn read_external_tileset (
+	tileset : &mut TiledTileset,
+	path : &Path,
+	bytes : &[u8],
+) -> Result<(), MapError> {
+	let external = if is_xml(path) { parse_tsx(bytes)? } else { parse_tsj(bytes)? };
+
+	tileset.image = external.image;
+	tileset.columns = external.columns;
+	tileset.tiles = external.tiles;
+	Ok(())
+}
+
+// Tiled Map
+// =========================================================================
+
+type Properties = HashMap<String, String>;
+
+/// The parts of a Tiled map we care about, shared by both file formats
+struct TiledMap {
+	tile_size : Vec2,
+	tilesets : Vec<TiledTileset>,
+	layers : Vec<TiledLayer>,
+}
+
+#[derive(Default)]
+struct TiledTileset {
+	first_gid : u32,
+	source : Option<String>,
+	image : Option<String>,
+	columns : u32,
+	tiles : HashMap<u32, Properties>,
+}
+
+enum TiledLayer {
+	Tiles { name : String, width : usize, data : Vec<u32>, properties : Properties },
+	Objects (Vec<TiledObject>),
+}
+
+struct TiledObject {
+	name : String,
+	class : String,
+	position : Vec2,
+	size : Vec2,
+	gid : Option<u32>,
+	properties : Properties,
+}
+
+fn build_map (tiled : TiledMap) -> Result<MapAsset, MapError> {
+	let mut tilesets = tiled.tilesets;
+	tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+	let mut map = MapAsset::default();
+	let mut palette : HashMap<(u32, bool, Option<String>), usize> = HashMap::new();
+
+	for layer in tiled.layers {
+		match layer {
+			TiledLayer::Tiles { name, width, data, properties } => {
+				let kind = name.to_lowercase().parse()
+					.or_else(|_| properties.get("kind").map_or(Ok(LayerKind::Ground), |k| k.parse()))
+					.map_err(|_| MapError::Invalid(format!("layer '{name}' has an unknown kind")))?;
+				let collider = is_true(&properties, "collider");
+				let encounter = encounter_table(&properties);
+
+				let mut layer = MapLayer::new(kind);
+
+				for row in data.chunks(width.max(1)) {
+					let mut cells = Vec::with_capacity(row.len());
+
+					for &gid in row {
+						let gid = gid & !GID_FLAGS;
+						if gid == 0 {
+							cells.push(None);
+							continue;
+						}
+
+						let key = (gid, collider, encounter.clone());
+						let index = match palette.get(&key) {
+							Some(&index) => index,
+							None => {
+								let mut def = tile_def(&tilesets, gid)?;
+								def.collider |= collider;
+								if encounter.is_some() { def.encounter = encounter.clone(); }
+
+								map.tiles.push(def);
+								palette.insert(key, map.tiles.len() - 1);
+								map.tiles.len() - 1
+							}
+						};
+
+						cells.push(Some(index));
+					}
+
+					layer.cells.push(cells);
+				}
+
+				map.layers.push(layer);
+			}
+			TiledLayer::Objects(objects) => {
+				for object in objects {
+					if let Some(object) = map_object(&tilesets, tiled.tile_size, object, &mut map.warnings)? {
+						map.objects.push(object);
+					}
+				}
+			}
+		}
+	}
+
+	map.layers.sort_by_key(|layer| layer.kind);
+
+	if map.layers.is_empty() {
+		return Err(MapError::Empty);
+	}
+
+	Ok(map)
+}
+
+fn tile_def (
+	tilesets : &[TiledTileset],
+	gid : u32,
+) -> Result<TileDef, MapError> {
+	let tileset = tilesets
+		.iter()
+		.rev()
+		.find(|tileset| tileset.first_gid <= gid)
+		.ok_or_else(|| MapError::Invalid(format!("tile {gid} has no tileset")))?;
+
+	let uses_tilesheet = tileset.image
+		.as_deref()
+		.is_some_and(|image| image.ends_with(TILESHEET_IMAGE));
+
+	if !uses_tilesheet || tileset.columns as usize != TILESHEET_COLUMNS {
+		return Err(MapError::Invalid(format!(
+			"tile {gid} is from a tileset that isn't {TILESHEET_IMAGE} ({TILESHEET_COLUMNS} columns)"
+		)));
+	}
+
+	let id = gid - tileset.first_gid;
+	let mut def = TileDef {
+		index: id as usize,
+		..default()
+	};
+
+	let Some(properties) = tileset.tiles.get(&id) else { return Ok(def) };
+
+	def.collider = is_true(properties, "collider");
+	def.encounter = encounter_table(properties);
+
+	if let Some(npc) = properties.get("npc") {
+		def.npc = Some(npc.parse().map_err(MapError::Invalid)?);
+	}
+
+	if let Some(tint) = properties.get("tint") {
+		def.tint = Some(parse_color(tint)?);
+	}
+
+	if let Some(autotile) = properties.get("autotile") {
+		def.autotile = Some(autotile.clone());
+	}
+
+	// Autotiled tiles take their frames from their pieces, the same as in
+	// text maps
+	let fps = properties
+		.get("fps")
+		.map(|fps| fps.parse().map_err(|_| MapError::Invalid(format!("invalid fps '{fps}'"))))
+		.transpose()?;
+
+	match (properties.get("frames"), is_true(properties, "animated")) {
+		(Some(_), true) => {
+			return Err(MapError::Invalid(format!("tile {gid} has both frames and animated")));
+		}
+		(Some(_), false) if def.autotile.is_some() => {
+			return Err(MapError::Invalid(format!(
+				"tile {gid} is autotiled, so its frames come from its autotile pieces"
+			)));
+		}
+		(Some(frames), false) => {
+			def.animation = Some(TileAnimation::parse(frames, fps).map_err(MapError::Invalid)?);
+		}
+		(None, true) if def.autotile.is_none() => {
+			return Err(MapError::Invalid(format!("tile {gid} is animated but not autotiled, so has no frames")));
+		}
+		(None, true) => {
+			def.animation = Some(TileAnimation::autotiled(fps).map_err(MapError::Invalid)?);
+		}
+		(None, false) => {}
+	}
+
+	if let Some(tags) = properties.get("tags") {
+		def.tags = tags
+			.split(',')
+			.map(str::trim)
+			.filter(|tag| !tag.is_empty())
+			.map(String::from)
+			.collect();
+	}
+
+	Ok(def)
+}
+
+/// Reads a map object, or `None` if it should be skipped. Objects that are
+/// skipped because of a mistake in the map add to `warnings`.
+fn map_object (
+	tilesets : &[TiledTileset],
+	tile_size : Vec2,
+	object : TiledObject,
+	warnings : &mut Vec<String>,
+) -> Result<Option<MapObject>, MapError> {
+	let mut position = object.position;
+
+	// Tile objects are anchored bottom-left, everything else top-left
+	if object.gid.is_some() {
+		position.y -= object.size.y;
+	}
+
+	let cell = (position / tile_size).floor().as_ivec2();
+	let size = (object.size / tile_size).round().max(Vec2::ONE).as_uvec2();
+
+	let kind = match object.class.as_str() {
+		"npc" => {
+			let npc = object.properties
+				.get("npc")
+				.unwrap_or(&object.name)
+				.parse()
+				.map_err(MapError::Invalid)?;
+
+			// NPCs are drawn with the object's tile, so they have to be
+			// placed as tile objects
+			let Some(gid) = object.gid else {
+				warnings.push(format!(
+					"npc '{}' at {}, {} has no tile, place it with the tile tool",
+					object.name, cell.x, cell.y,
+				));
+				return Ok(None);
+			};
+
+			MapObjectKind::Npc { npc, tile: tile_def(tilesets, gid & !GID_FLAGS)?.index }
+		}
+		"spawn" => MapObjectKind::Spawn,
+		"trigger" => MapObjectKind::Trigger { properties: object.properties },
+		"warp" => {
+			let property = |name : &str| object.properties
+				.get(name)
+				.cloned()
+				.ok_or_else(|| MapError::Invalid(format!("warp '{}' has no {name} property", object.name)));
+
+			MapObjectKind::Warp(Warp {
+				map: property("map")?,
+				spawn: property("spawn")?,
+			})
+		}
+		class => {
+			warnings.push(format!(
+				"object '{}' at {}, {} has unknown class '{class}', so was skipped",
+				object.name, cell.x, cell.y,
+			));
+			return Ok(None);
+		}
+	};
+
+	Ok(Some(MapObject {
+		name: object.name,
+		kind,
+		cell,
+		size,
+	}))
+}
+
+// JSON (.tmj / .tsj)
+// =========================================================================
+
+#[derive(Deserialize)]
+struct JsonMap {
+	#[serde(default)]
+	orientation : String,
+	#[serde(default)]
+	infinite : bool,
+	tilewidth : f32,
+	tileheight : f32,
+	#[serde(default)]
+	tilesets : Vec<JsonTileset>,
+	layers : Vec<JsonLayer>,
+}
+
+#[derive(Deserialize)]
+struct JsonTileset {
+	#[serde(default)]
+	firstgid : u32,
+	source : Option<String>,
+	image : Option<String>,
+	#[serde(default)]
+	columns : u32,
+	#[serde(default)]
+	tiles : Vec<JsonTile>,
+}
+
+#[derive(Deserialize)]
+struct JsonTile {
+	id : u32,
+	#[serde(default)]
+	properties : Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonLayer {
+	TileLayer {
+		name : String,
+		width : usize,
+		data : Option<JsonLayerData>,
+		#[serde(default)]
+		properties : Vec<JsonProperty>,
+	},
+	ObjectGroup {
+		objects : Vec<JsonObject>,
+	},
+	Group {
+		layers : Vec<JsonLayer>,
+	},
+	#[serde(other)]
+	Other,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonLayerData {
+	Gids (Vec<u32>),
+	Encoded (serde::de::IgnoredAny),
+}
+
+#[derive(Deserialize)]
+struct JsonObject {
+	#[serde(default)]
+	name : String,
+	#[serde(default, rename = "type")]
+	kind : String,
+	#[serde(default)]
+	class : String,
+	x : f32,
+	y : f32,
+	#[serde(default)]
+	width : f32,
+	#[serde(default)]
+	height : f32,
+	gid : Option<u32>,
+	#[serde(default)]
+	properties : Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonProperty {
+	name : String,
+	value : serde_json::Value,
+}
+
+fn parse_tmj (bytes : &[u8]) -> Result<TiledMap, MapError> {
+	let json : JsonMap = serde_json::from_slice(bytes)
+		.map_err(|e| MapError::Invalid(format!("invalid Tiled map: {e}")))?;
+
+	check_map_supported(&json.orientation, json.infinite)?;
+
+	let mut layers = Vec::new();
+	flatten_json_layers(json.layers, &mut layers)?;
+
+	Ok(TiledMap {
+		tile_size: Vec2::new(json.tilewidth, json.tileheight),
+		tilesets: json.tilesets.into_iter().map(json_tileset).collect(),
+		layers,
+	})
+}
+
+fn parse_tsj (bytes : &[u8]) -> Result<TiledTileset, MapError> {
+	let json : JsonTileset = serde_json::from_slice(bytes)
+		.map_err(|e| MapError::Invalid(format!("invalid Tiled tileset: {e}")))?;
+
+	Ok(json_tileset(json))
+}
+
+fn json_tileset (json : JsonTileset) -> TiledTileset {
+	TiledTileset {
+		first_gid: json.firstgid,
+		source: json.source,
+		image: json.image,
+		columns: json.columns,
+		tiles: json.tiles
+			.into_iter()
+			.map(|tile| (tile.id, json_properties(tile.properties)))
+			.collect(),
+	}
+}
+
+fn flatten_json_layers (
+	json_layers : Vec<JsonLayer>,
+	layers : &mut Vec<TiledLayer>,
+) -> Result<(), MapError> {
+	for layer in json_layers {
+		match layer {
+			JsonLayer::TileLayer { name, width, data, properties } => {
+				let data = match data {
+					Some(JsonLayerData::Gids(gids)) => gids,
+					Some(JsonLayerData::Encoded(_)) => return Err(MapError::Invalid(format!(
+						"layer '{name}' is compressed, save it with the CSV layer format"
+					))),
+					None => Vec::new(),
+				};
+
+				layers.push(TiledLayer::Tiles {
+					name,
+					width,
+					data,
+					properties: json_properties(properties),
+				});
+			}
+			JsonLayer::ObjectGroup { objects } => {
+				layers.push(TiledLayer::Objects(objects.into_iter().map(|object| TiledObject {
+					name: object.name,
+					class: if object.kind.is_empty() { object.class } else { object.kind },
+					position: Vec2::new(object.x, object.y),
+					size: Vec2::new(object.width, object.height),
+					gid: object.gid,
+					properties: json_properties(object.properties),
+				}).collect()));
+			}
+			JsonLayer::Group { layers: children } => flatten_json_layers(children, layers)?,
+			JsonLayer::Other => {}
+		}
+	}
+
+	Ok(())
+}
+
+fn json_properties (properties : Vec<JsonProperty>) -> Properties {
+	properties
+		.into_iter()
+		.map(|property| {
+			let value = match property.value {
+				serde_json::Value::String(value) => value,
+				value => value.to_string(),
+			};
+
+			(property.name, value)
+		})
+		.collect()
+}
+
+// XML (.tmx / .tsx)
+// =========================================================================
+
+fn parse_tmx (bytes : &[u8]) -> Result<TiledMap, MapError> {
+	let text = std::str::from_utf8(bytes).map_err(|_| MapError::InvalidUtf8)?;
+	let doc = roxmltree::Document::parse(text)
+		.map_err(|e| MapError::Invalid(format!("invalid Tiled map: {e}")))?;
+	let root = doc.root_element();
+
+	check_map_supported(
+		root.attribute("orientation").unwrap_or_default(),
+		root.attribute("infinite") == Some("1"),
+	)?;
+
+	let mut tilesets = Vec::new();
+	for node in root.children().filter(|n| n.has_tag_name("tileset")) {
+		let mut tileset = xml_tileset(node)?;
+		tileset.first_gid = attr(node, "firstgid")?;
+		tileset.source = node.attribute("source").map(String::from);
+		tilesets.push(tileset);
+	}
+
+	let mut layers = Vec::new();
+	flatten_xml_layers(root, &mut layers)?;
+
+	Ok(TiledMap {
+		tile_size: Vec2::new(attr(root, "tilewidth")?, attr(root, "tileheight")?),
+		tilesets,
+		layers,
+	})
+}
+
+fn parse_tsx (bytes : &[u8]) -> Result<TiledTileset, MapError> {
+	let text = std::str::from_utf8(bytes).map_err(|_| MapError::InvalidUtf8)?;
+	let doc = roxmltree::Document::parse(text)
+		.map_err(|e| MapError::Invalid(format!("invalid Tiled tileset: {e}")))?;
+
+	xml_tileset(doc.root_element())
+}
+
+fn xml_tileset (node : roxmltree::Node) -> Result<TiledTileset, MapError> {
+	let mut tileset = TiledTileset {
+		columns: node.attribute("columns").map_or(Ok(0), |_| attr(node, "columns"))?,
+		image: node
+			.children()
+			.find(|n| n.has_tag_name("image"))
+			.and_then(|n| n.attribute("source"))
+			.map(String::from),
+		..default()
+	};
+
+	for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+		tileset.tiles.insert(attr(tile, "id")?, xml_properties(tile));
+	}
+
+	Ok(tileset)
+}
+
+fn flatten_xml_layers (
+	parent : roxmltree::Node,
+	layers : &mut Vec<TiledLayer>,
+) -> Result<(), MapError> {
+	for node in parent.children().filter(|n| n.is_element()) {
+		match node.tag_name().name() {
+			"layer" => {
+				let name = node.attribute("name").unwrap_or_default().to_string();
+				let data = match node.children().find(|n| n.has_tag_name("data")) {
+					Some(data) => xml_layer_data(&name, data)?,
+					None => Vec::new(),
+				};
+
+				layers.push(TiledLayer::Tiles {
+					width: attr(node, "width")?,
+					data,
+					properties: xml_properties(node),
+					name,
+				});
+			}
+			"objectgroup" => {
+				let mut objects = Vec::new();
+
+				for object in node.children().filter(|n| n.has_tag_name("object")) {
+					let class = object.attribute("type")
+						.or_else(|| object.attribute("class"))
+						.unwrap_or_default();
+
+					objects.push(TiledObject {
+						name: object.attribute("name").unwrap_or_default().to_string(),
+						class: class.to_string(),
+						position: Vec2::new(attr(object, "x")?, attr(object, "y")?),
+						size: Vec2::new(attr_or(object, "width", 0.)?, attr_or(object, "height", 0.)?),
+						gid: object.attribute("gid").map(|_| attr(object, "gid")).transpose()?,
+						properties: xml_properties(object),
+					});
+				}
+
+				layers.push(TiledLayer::Objects(objects));
+			}
+			"group" => flatten_xml_layers(node, layers)?,
+			_ => {}
+		}
+	}
+
+	Ok(())
+}
+
+fn xml_layer_data (name : &str, data : roxmltree::Node) -> Result<Vec<u32>, MapError> {
+	match data.attribute("encoding") {
+		Some("csv") => data
+			.text()
+			.unwrap_or_default()
+			.split(',')
+			.map(str::trim)
+			.filter(|gid| !gid.is_empty())
+			.map(|gid| gid.parse().map_err(|_| MapError::Invalid(format!(
+				"layer '{name}' has an invalid tile '{gid}'"
+			))))
+			.collect(),
+		None => data
+			.children()
+			.filter(|n| n.has_tag_name("tile"))
+			.map(|tile| attr_or(tile, "gid", 0))
+			.collect(),
+		Some(_) => Err(MapError::Invalid(format!(
+			"layer '{name}' is compressed, save it with the CSV layer format"
+		))),
+	}
+}
+
+fn xml_properties (node : roxmltree::Node) -> Properties {
+	node
+		.children()
+		.filter(|n| n.has_tag_name("properties"))
+		.flat_map(|n| n.children().filter(|n| n.has_tag_name("property")))
+		.filter_map(|property| {
+			let value = property.attribute("value").or_else(|| property.text())?;
+			Some((property.attribute("name")?.to_string(), value.to_string()))
+		})
+		.collect()
+}
+
+fn attr<T : FromStr> (node : roxmltree::Node, name : &str) -> Result<T, MapError> {
+	node
+		.attribute(name)
+		.and_then(|value| value.parse().ok())
+		.ok_or_else(|| MapError::Invalid(format!(
+			"<{}> has a missing or invalid '{name}'", node.tag_name().name()
+		)))
+}
+
+fn attr_or<T : FromStr> (node : roxmltree::Node, name : &str, fallback : T) -> Result<T, MapError> {
+	match node.attribute(name) {
+		Some(_) => attr(node, name),
+		None => Ok(fallback),
+	}
+}
+
+// Helpers
+// =========================================================================
+
+fn is_xml (path : &Path) -> bool {
+	matches!(
+		path.extension().and_then(|ext| ext.to_str()),
+		Some("tmx" | "tsx"),
+	)
+}
+
+fn check_map_supported (orientation : &str, infinite : bool) -> Result<(), MapError> {
+	if !orientation.is_empty() && orientation != "orthogonal" {
+		return Err(MapError::Invalid(format!("{orientation} maps aren't supported")));
+	}
+
+	if infinite {
+		return Err(MapError::Invalid("infinite maps aren't supported".into()));
+	}
+
+	Ok(())
+}
+
+fn is_true (properties : &Properties, name : &str) -> bool {
+	properties.get(name).is_some_and(|value| value == "true")
+}
+
+/// The encounter table named by an `encounter` property. `true` means the
+/// default table.
+fn encounter_table (properties : &Properties) -> Option<String> {
+	match properties.get("encounter")?.as_str() {
+		"false" | "" => None,
+		"true" => Some(DEFAULT_ENCOUNTERS.to_string()),
+		table => Some(table.to_string()),
+	}
+}
+
+/// Tiled writes colours as `#AARRGGBB` or `#RRGGBB`
+fn parse_color (value : &str) -> Result<Color, MapError> {
+	let invalid = || MapError::Invalid(format!("invalid tint '{value}'"));
+
+	// Checked first so the alpha can be sliced off by byte
+	let hex = value.trim_start_matches('#');
+	if !hex.is_ascii() { return Err(invalid()); }
+
+	let hex = match hex.len() {
+		8 => format!("{}{}", &hex[2..], &hex[..2]),
+		_ => hex.to_string(),
+	};
+
+	Color::hex(hex).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::npc::Npc;
+
+	/// A 3x2 map using the tilesheet, with `layers` and `tiles` spliced in
+	fn tmj (tiles : &str, layers : &str) -> String {
+		format!(r#"{{
+			"orientation": "orthogonal",
+			"tilewidth": 16,
+			"tileheight": 16,
+			"tilesets": [{{
+				"firstgid": 1,
+				"image": "../tilesheet.png",
+				"columns": 49,
+				"tiles": [{tiles}]
+			}}],
+			"layers": [{layers}]
+		}}"#)
+	}
+
+	fn load_tmj (json : &str) -> Result<MapAsset, MapError> {
+		build_map(parse_tmj(json.as_bytes())?)
+	}
+
+	fn load_tmx (xml : &str) -> Result<MapAsset, MapError> {
+		build_map(parse_tmx(xml.as_bytes())?)
+	}
+
+	fn invalid (result : Result<MapAsset, MapError>) -> String {
+		match result {
+			Err(MapError::Invalid(message)) => message,
+			other => panic!("expected an invalid map, got {other:?}"),
+		}
+	}
+
+	const GROUND : &str = r#"{ "type": "tilelayer", "name": "ground", "width": 3, "data": [1, 2, 0, 2, 1, 1] }"#;
+
+	// JSON
+	// ---------------------------------------------------------------------
+
+	#[test]
+	fn tmj_reads_tile_layers () {
+		let map = load_tmj(&tmj("", GROUND)).unwrap();
+		let tile = |x : usize, y : usize| map.layers[0].cells[y][x].map(|index| map.tiles[index].index);
+
+		assert_eq!(map.layers.len(), 1);
+		assert_eq!(map.layers[0].kind, LayerKind::Ground);
+		assert_eq!(tile(0, 0), Some(0));
+		assert_eq!(tile(1, 0), Some(1));
+		assert_eq!(tile(2, 0), None);
+		assert_eq!(tile(0, 1), Some(1));
+		assert_eq!(map.tiles.len(), 2);
+	}
+
+	#[test]
+	fn tmj_strips_flip_flags_from_gids () {
+		let flipped = (1 | 0x8000_0000u32).to_string();
+		let layer = format!(r#"{{ "type": "tilelayer", "name": "ground", "width": 1, "data": [{flipped}] }}"#);
+		let map = load_tmj(&tmj("", &layer)).unwrap();
+
+		assert_eq!(map.tiles[0].index, 0);
+	}
+
+	#[test]
+	fn tmj_reads_tile_properties () {
+		let tiles = r##"{ "id": 1, "properties": [
+			{ "name": "collider", "type": "bool", "value": true },
+			{ "name": "encounter", "type": "string", "value": "meadow" },
+			{ "name": "npc", "type": "string", "value": "recruit:knight" },
+			{ "name": "tint", "type": "color", "value": "#806ED57E" },
+			{ "name": "frames", "type": "string", "value": "1, 2" },
+			{ "name": "fps", "type": "float", "value": 2 },
+			{ "name": "tags", "type": "string", "value": "town, ,wall" }
+		] }"##;
+
+		let map = load_tmj(&tmj(tiles, GROUND)).unwrap();
+		let def = map.tiles.iter().find(|def| def.index == 1).unwrap();
+		let animation = def.animation.as_ref().unwrap();
+
+		assert!(def.collider);
+		assert_eq!(def.encounter.as_deref(), Some("meadow"));
+		assert!(matches!(&def.npc, Some(Npc::Recruit(id)) if id == "knight"));
+		assert_eq!(def.tint, Some(Color::hex("6ED57E80").unwrap()));
+		assert_eq!(animation.frames, [1, 2]);
+		assert_eq!(animation.fps, 2.);
+		assert_eq!(def.tags, ["town", "wall"]);
+	}
+
+	#[test]
+	fn tmj_reads_autotiled_animation () {
+		let tiles = r#"{ "id": 1, "properties": [
+			{ "name": "autotile", "type": "string", "value": "water" },
+			{ "name": "animated", "type": "bool", "value": true },
+			{ "name": "fps", "type": "float", "value": 1.5 }
+		] }"#;
+
+		let map = load_tmj(&tmj(tiles, GROUND)).unwrap();
+		let def = map.tiles.iter().find(|def| def.index == 1).unwrap();
+		let animation = def.animation.as_ref().unwrap();
+
+		assert_eq!(def.autotile.as_deref(), Some("water"));
+		assert!(animation.frames.is_empty());
+		assert_eq!(animation.fps, 1.5);
+	}
+
+	#[test]
+	fn tmj_applies_layer_properties_to_every_tile () {
+		let layer = r#"{ "type": "tilelayer", "name": "Grass", "width": 2, "data": [1, 2], "properties": [
+			{ "name": "kind", "type": "string", "value": "decoration" },
+			{ "name": "collider", "type": "bool", "value": true },
+			{ "name": "encounter", "type": "bool", "value": true }
+		] }"#;
+
+		let map = load_tmj(&tmj("", layer)).unwrap();
+
+		assert_eq!(map.layers[0].kind, LayerKind::Decoration);
+		assert!(map.tiles.iter().all(|def| def.collider));
+		assert!(map.tiles.iter().all(|def| def.encounter.as_deref() == Some(DEFAULT_ENCOUNTERS)));
+	}
+
+	#[test]
+	fn tmj_reads_objects () {
+		let objects = r#"{ "type": "group", "layers": [{ "type": "objectgroup", "objects": [
+			{ "name": "start", "type": "spawn", "x": 16, "y": 0 },
+			{ "name": "door", "class": "warp", "x": 32, "y": 16, "width": 16, "height": 16, "properties": [
+				{ "name": "map", "type": "string", "value": "house" },
+				{ "name": "spawn", "type": "string", "value": "door" }
+			] },
+			{ "name": "healer", "type": "npc", "gid": 81, "x": 0, "y": 32, "width": 16, "height": 16 }
+		] }] }"#;
+
+		let map = load_tmj(&tmj("", &format!("{GROUND}, {objects}"))).unwrap();
+
+		assert_eq!(map.objects.len(), 3);
+		assert!(matches!(map.objects[0].kind, MapObjectKind::Spawn));
+		assert_eq!(map.objects[0].cell, IVec2::new(1, 0));
+
+		match &map.objects[1].kind {
+			MapObjectKind::Warp(warp) => assert_eq!((warp.map.as_str(), warp.spawn.as_str()), ("house", "door")),
+			kind => panic!("expected a warp, got {kind:?}"),
+		}
+		assert_eq!(map.objects[1].cell, IVec2::new(2, 1));
+
+		// Tile objects are anchored on their bottom-left corner
+		assert!(matches!(map.objects[2].kind, MapObjectKind::Npc { npc: Npc::Healer, tile: 80 }));
+		assert_eq!(map.objects[2].cell, IVec2::new(0, 1));
+		assert!(map.warnings.is_empty());
+	}
+
+	#[test]
+	fn npc_without_a_tile_is_skipped_with_a_warning () {
+		let objects = r#"{ "type": "objectgroup", "objects": [
+			{ "name": "healer", "type": "npc", "x": 16, "y": 16 }
+		] }"#;
+
+		let map = load_tmj(&tmj("", &format!("{GROUND}, {objects}"))).unwrap();
+
+		assert!(map.objects.is_empty());
+		assert_eq!(map.warnings, ["npc 'healer' at 1, 1 has no tile, place it with the tile tool"]);
+	}
+
+	#[test]
+	fn unknown_class_is_skipped_with_a_warning () {
+		let objects = r#"{ "type": "objectgroup", "objects": [
+			{ "name": "door", "type": "wrap", "x": 32, "y": 16 }
+		] }"#;
+
+		let map = load_tmj(&tmj("", &format!("{GROUND}, {objects}"))).unwrap();
+
+		assert!(map.objects.is_empty());
+		assert_eq!(map.warnings, ["object 'door' at 2, 1 has unknown class 'wrap', so was skipped"]);
+	}
+
+	#[test]
+	fn warp_needs_map_and_spawn () {
+		let objects = r#"{ "type": "objectgroup", "objects": [
+			{ "name": "door", "type": "warp", "x": 0, "y": 0, "properties": [
+				{ "name": "map", "type": "string", "value": "house" }
+			] }
+		] }"#;
+
+		assert_eq!(invalid(load_tmj(&tmj("", &format!("{GROUND}, {objects}")))), "warp 'door' has no spawn property");
+	}
+
+	#[test]
+	fn tmj_rejects_unsupported_maps () {
+		let layer = r#"{ "type": "tilelayer", "name": "ground", "width": 1, "data": "eJxjZGBgAAAABAAB" }"#;
+		assert_eq!(
+			invalid(load_tmj(&tmj("", layer))),
+			"layer 'ground' is compressed, save it with the CSV layer format",
+		);
+
+		// Layers named after no kind are ground, unless they say otherwise
+		let layer = r#"{ "type": "tilelayer", "name": "Sky", "width": 1, "data": [1], "properties": [
+			{ "name": "kind", "type": "string", "value": "sky" }
+		] }"#;
+		assert_eq!(invalid(load_tmj(&tmj("", layer))), "layer 'Sky' has an unknown kind");
+
+		let map = tmj("", GROUND).replace("orthogonal", "isometric");
+		assert_eq!(invalid(load_tmj(&map)), "isometric maps aren't supported");
+
+		let map = tmj("", GROUND).replacen('{', r#"{ "infinite": true,"#, 1);
+		assert_eq!(invalid(load_tmj(&map)), "infinite maps aren't supported");
+
+		let map = tmj("", GROUND).replace("\"columns\": 49", "\"columns\": 8");
+		assert_eq!(
+			invalid(load_tmj(&map)),
+			"tile 1 is from a tileset that isn't tilesheet.png (49 columns)",
+		);
+	}
+
+	#[test]
+	fn map_without_layers_is_empty () {
+		assert!(matches!(load_tmj(&tmj("", "")), Err(MapError::Empty)));
+	}
+
+	// XML
+	// ---------------------------------------------------------------------
+
+	const TMX : &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map orientation="orthogonal" infinite="0" tilewidth="16" tileheight="16">
+	<tileset firstgid="1" columns="49">
+		<image source="../tilesheet.png"/>
+		<tile id="1">
+			<properties>
+				<property name="collider" type="bool" value="true"/>
+				<property name="tint" value="#FF0000"/>
+			</properties>
+		</tile>
+	</tileset>
+	<group name="layers">
+		<layer name="ground" width="3">
+			<data encoding="csv">1,2,0,
+2,1,1</data>
+		</layer>
+		<layer name="overhead" width="1">
+			<data><tile gid="2"/></data>
+		</layer>
+	</group>
+	<objectgroup>
+		<object name="start" type="spawn" x="16" y="16"/>
+		<object name="healer" class="npc" gid="81" x="32" y="32" width="16" height="16"/>
+	</objectgroup>
+</map>"##;
+
+	#[test]
+	fn tmx_reads_layers_and_tiles () {
+		let map = load_tmx(TMX).unwrap();
+		let ground = &map.layers[0];
+
+		assert_eq!(map.layers.len(), 2);
+		assert_eq!(ground.kind, LayerKind::Ground);
+		assert_eq!(map.layers[1].kind, LayerKind::Overhead);
+		assert_eq!(ground.cells.len(), 2);
+		assert_eq!(ground.cells[0][2], None);
+
+		let def = &map.tiles[ground.cells[0][1].unwrap()];
+		assert_eq!(def.index, 1);
+		assert!(def.collider);
+		assert_eq!(def.tint, Some(Color::hex("FF0000").unwrap()));
+	}
+
+	#[test]
+	fn tmx_reads_objects () {
+		let map = load_tmx(TMX).unwrap();
+
+		assert_eq!(map.objects.len(), 2);
+		assert!(matches!(map.objects[0].kind, MapObjectKind::Spawn));
+		assert_eq!(map.objects[0].cell, IVec2::new(1, 1));
+		assert!(matches!(map.objects[1].kind, MapObjectKind::Npc { npc: Npc::Healer, tile: 80 }));
+		assert_eq!(map.objects[1].cell, IVec2::new(2, 1));
+	}
+
+	#[test]
+	fn tmx_rejects_bad_data () {
+		let map = TMX.replace(r#"encoding="csv""#, r#"encoding="base64""#);
+		assert_eq!(
+			invalid(load_tmx(&map)),
+			"layer 'ground' is compressed, save it with the CSV layer format",
+		);
+
+		let map = TMX.replace("2,1,1", "2,1,x");
+		assert_eq!(invalid(load_tmx(&map)), "layer 'ground' has an invalid tile 'x'");
+
+		let map = TMX.replace(r#"tilewidth="16""#, "");
+		assert_eq!(invalid(load_tmx(&map)), "<map> has a missing or invalid 'tilewidth'");
+	}
+
+	// Colours
+	// ---------------------------------------------------------------------
+
+	#[test]
+	fn colours_move_alpha_to_the_end () {
+		assert_eq!(parse_color("#80FF0000").unwrap(), Color::hex("FF000080").unwrap());
+		assert_eq!(parse_color("#FF0000").unwrap(), Color::hex("FF0000").unwrap());
+		assert_eq!(parse_color("FF0000").unwrap(), Color::hex("FF0000").unwrap());
+	}
+
+	#[test]
+	fn bad_colours_are_errors () {
+		assert!(matches!(parse_color("#éAABBCC"), Err(MapError::Invalid(_))));
+		assert!(matches!(parse_color("#GG0000"), Err(MapError::Invalid(_))));
+		assert!(matches!(parse_color(""), Err(MapError::Invalid(_))));
+	}
+}
@@ -0,0 +1,323 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use bevy::asset::FileAssetIo;
+use bevy::prelude::*;
+use crate::core::tilemap::{LayerKind, MapAsset, MapError, MapObjectKind, text, tiled};
+use crate::core::tilemap::grid::MapGrid;
+
+/// Where the maps live, relative to the project root
+const MAPS_DIR : &str = "assets/maps";
+
+/// What `validate_map` found wrong with a map. Errors are mistakes that
+/// break the map in game, warnings are worth a look but might be on purpose.
+#[derive(Debug, Default)]
+pub struct MapReport {
+	pub errors : Vec<String>,
+	pub warnings : Vec<String>,
+}
+
+// Validation
+// =========================================================================
+
+/// Checks a map for mistakes that would otherwise only show up in game.
+/// `maps` holds every map by the name warps use for it, so warps can be
+/// checked against the map they lead to.
+pub fn validate_map (
+	map : &MapAsset,
+	maps : &HashMap<String, MapAsset>,
+) -> MapReport {
+	let mut report = MapReport::default();
+
+	// Anything the parser had to skip over, like characters missing from
+	// the legend, leaves a hole in the map
+	report.errors.extend(map.warnings.iter().cloned());
+
+	for layer in &map.layers {
+		let width = layer.cells.iter().map(Vec::len).max().unwrap_or(0);
+		let ragged : Vec<String> = layer.cells
+			.iter()
+			.enumerate()
+			.filter(|(_, row)| row.len() != width)
+			.map(|(y, _)| y.to_string())
+			.collect();
+
+		if ragged.is_empty() { continue; }
+
+		let message = format!(
+			"{:?} layer rows {} are shorter than the layer's {width} cells",
+			layer.kind,
+			ragged.join(", "),
+		);
+
+		// Editors strip trailing spaces, so short rows on the upper layers
+		// are usually just empty cells. On the ground they leave holes.
+		if layer.kind == LayerKind::Ground {
+			report.errors.push(message);
+		} else {
+			report.warnings.push(message);
+		}
+	}
+
+	let grid = MapGrid::from_map(map);
+	let mut spawns = Vec::new();
+
+	for object in &map.objects {
+		let cell = object.cell;
+
+		match &object.kind {
+			MapObjectKind::Spawn => {
+				match grid.get(cell) {
+					None => report.errors.push(format!(
+						"spawn point '{}' at {}, {} is outside the map",
+						object.name, cell.x, cell.y,
+					)),
+					Some(grid_cell) if grid_cell.collider => report.errors.push(format!(
+						"spawn point '{}' at {}, {} is inside a collider",
+						object.name, cell.x, cell.y,
+					)),
+					Some(_) => {}
+				}
+
+				spawns.push(cell);
+			}
+			MapObjectKind::Warp(warp) => {
+				match maps.get(&warp.map) {
+					None => report.errors.push(format!(
+						"warp at {}, {} leads to missing map '{}'",
+						cell.x, cell.y, warp.map,
+					)),
+					Some(target) if !has_spawn(target, &warp.spawn) => report.errors.push(format!(
+						"warp at {}, {} leads to missing spawn point '{}' on '{}'",
+						cell.x, cell.y, warp.spawn, warp.map,
+					)),
+					Some(_) => {}
+				}
+			}
+			_ => {}
+		}
+	}
+
+	if spawns.is_empty() {
+		report.errors.push("map has no spawn points".to_string());
+	} else {
+		let unreachable = unreachable_cells(map, &grid, &spawns);
+
+		if let Some(first) = unreachable.first() {
+			report.warnings.push(format!(
+				"{} cells can't be reached from any spawn point, starting at {}, {}",
+				unreachable.len(), first.x, first.y,
+			));
+		}
+	}
+
+	report
+}
+
+/// Validates every map in `assets/maps`, printing what was found. Returns
+/// false if any map has errors.
+pub fn validate_all_maps () -> bool {
+	let dir = FileAssetIo::get_base_path().join(MAPS_DIR);
+	let entries = match std::fs::read_dir(&dir) {
+		Ok(entries) => entries,
+		Err(e) => {
+			eprintln!("could not read {}: {e}", dir.display());
+			return false;
+		}
+	};
+
+	let mut paths : Vec<PathBuf> = entries
+		.filter_map(|entry| Some(entry.ok()?.path()))
+		.filter(|path| map_name(path).is_some())
+		.collect();
+	paths.sort();
+
+	let mut passed = true;
+	let mut maps = HashMap::new();
+	let mut names = Vec::new();
+
+	for path in paths {
+		let name = map_name(&path).expect("Paths were filtered to maps");
+
+		match read_map(&path) {
+			Ok(map) => {
+				maps.insert(name.clone(), map);
+				names.push(name);
+			}
+			Err(e) => {
+				eprintln!("{name}\n  error: {e}");
+				passed = false;
+			}
+		}
+	}
+
+	for name in names {
+		let report = validate_map(&maps[&name], &maps);
+
+		if report.errors.is_empty() && report.warnings.is_empty() {
+			println!("{name}: ok");
+			continue;
+		}
+
+		println!("{name}");
+		for error in &report.errors { println!("  error: {error}"); }
+		for warning in &report.warnings { println!("  warning: {warning}"); }
+
+		passed &= report.errors.is_empty();
+	}
+
+	passed
+}
+
+// Helpers
+// =========================================================================
+
+/// The name `load_map` knows a map file by, or `None` if it isn't a map
+fn map_name (path : &Path) -> Option<String> {
+	let file_name = path.file_name()?.to_str()?;
+
+	match path.extension()?.to_str()? {
+		"txt" => Some(path.file_stem()?.to_str()?.to_string()),
+		"tmj" | "tmx" => Some(file_name.to_string()),
+		_ => None,
+	}
+}
+
+fn read_map (path : &Path) -> Result<MapAsset, MapError> {
+	if path.extension().is_some_and(|ext| ext == "txt") {
+		let bytes = std::fs::read(path)
+			.map_err(|e| MapError::Invalid(format!("could not read {}: {e}", path.display())))?;
+
+		text::parse_text_map(&bytes)
+	} else {
+		tiled::read_tiled_map(path)
+	}
+}
+
+fn has_spawn (map : &MapAsset, name : &str) -> bool {
+	map.objects
+		.iter()
+		.any(|object| matches!(object.kind, MapObjectKind::Spawn) && object.name == name)
+}
+
+/// Cells that have a tile and no collider, but can't be walked to from any
+/// of `spawns`
+fn unreachable_cells (map : &MapAsset, grid : &MapGrid, spawns : &[IVec2]) -> Vec<IVec2> {
+	let size = grid.size();
+	let index = |cell : IVec2| (cell.y as u32 * size.x + cell.x as u32) as usize;
+
+	let has_tile = |cell : IVec2| map.layers.iter().any(|layer| {
+		layer.cells
+			.get(cell.y as usize)
+			.and_then(|row| row.get(cell.x as usize))
+			.is_some_and(Option::is_some)
+	});
+	let walkable = |cell : IVec2| {
+		grid.get(cell).is_some_and(|grid_cell| !grid_cell.collider) && has_tile(cell)
+	};
+
+	let mut reached = vec![false; (size.x * size.y) as usize];
+	let mut queue : VecDeque<IVec2> = spawns.iter().copied().filter(|&cell| walkable(cell)).collect();
+	for &cell in &queue { reached[index(cell)] = true; }
+
+	while let Some(cell) = queue.pop_front() {
+		for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+			let next = cell + offset;
+			if !walkable(next) || reached[index(next)] { continue; }
+
+			reached[index(next)] = true;
+			queue.push_back(next);
+		}
+	}
+
+	(0..size.y as i32)
+		.flat_map(|y| (0..size.x as i32).map(move |x| IVec2::new(x, y)))
+		.filter(|&cell| walkable(cell) && !reached[index(cell)])
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const LEGEND : &str = "[legend]\n. tile=0\n# tile=169 collider\n";
+
+	fn map (text : &str) -> MapAsset {
+		text::parse_text_map(format!("{LEGEND}{text}").as_bytes()).expect("Map should parse")
+	}
+
+	fn validate (text : &str) -> MapReport {
+		validate_map(&map(text), &HashMap::new())
+	}
+
+	#[test]
+	fn clean_map_has_nothing_to_report () {
+		let report = validate("[map]\n###\n#.#\n###\n[spawns]\nstart 1 1\n");
+
+		assert!(report.errors.is_empty(), "{:?}", report.errors);
+		assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+	}
+
+	#[test]
+	fn unknown_char_is_an_error () {
+		let report = validate("[map]\n.x\n[spawns]\nstart 0 0\n");
+
+		assert_eq!(report.errors, ["character 'x' at 1, 0 is not in the legend"]);
+	}
+
+	#[test]
+	fn missing_spawn_is_an_error () {
+		let report = validate("[map]\n..\n");
+
+		assert_eq!(report.errors, ["map has no spawn points"]);
+	}
+
+	#[test]
+	fn bad_spawns_are_errors () {
+		let report = validate("[map]\n.#\n[spawns]\nstart 1 0\nroof 0 5\n");
+
+		assert_eq!(report.errors, [
+			"spawn point 'start' at 1, 0 is inside a collider",
+			"spawn point 'roof' at 0, 5 is outside the map",
+		]);
+	}
+
+	#[test]
+	fn warps_need_a_map_and_spawn_to_lead_to () {
+		let mut maps = HashMap::new();
+		maps.insert("house".to_string(), map("[map]\n.\n[spawns]\nstart 0 0\n"));
+
+		let town = map("[map]\n...\n[spawns]\nstart 0 0\n[warps]\n1 0 house door\n2 0 cave start\n");
+		let report = validate_map(&town, &maps);
+
+		assert_eq!(report.errors, [
+			"warp at 1, 0 leads to missing spawn point 'door' on 'house'",
+			"warp at 2, 0 leads to missing map 'cave'",
+		]);
+
+		let town = map("[map]\n..\n[spawns]\nstart 0 0\n[warps]\n1 0 house start\n");
+		assert!(validate_map(&town, &maps).errors.is_empty());
+	}
+
+	#[test]
+	fn ragged_ground_rows_are_errors () {
+		let report = validate("[map]\n...\n.\n...\n[spawns]\nstart 0 0\n");
+
+		assert_eq!(report.errors, ["Ground layer rows 1 are shorter than the layer's 3 cells"]);
+	}
+
+	#[test]
+	fn ragged_upper_rows_are_warnings () {
+		let report = validate("[map]\n...\n...\n[layer overhead]\n...\n.\n[spawns]\nstart 0 0\n");
+
+		assert!(report.errors.is_empty(), "{:?}", report.errors);
+		assert_eq!(report.warnings, ["Overhead layer rows 1 are shorter than the layer's 3 cells"]);
+	}
+
+	#[test]
+	fn unreachable_cells_are_warnings () {
+		let report = validate("[map]\n.#..\n[spawns]\nstart 0 0\n");
+
+		assert!(report.errors.is_empty(), "{:?}", report.errors);
+		assert_eq!(report.warnings, ["2 cells can't be reached from any spawn point, starting at 2, 0"]);
+	}
+}
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use crate::core::tilemap::{Warp, WarpEvent};
 use crate::GameState;
 use crate::util::math::{clamp01, lerp3};
 
@@ -22,10 +23,22 @@ impl Plugin for TransitionPlugin {
 struct ScreenFade {
 	alpha : f32,
 	sent : bool,
-	next_state : Option<GameState>,
+	action : FadeAction,
 	timer : Timer,
 }
 
+/// What happens once the screen is fully faded out
+enum FadeAction {
+	/// Push the given state, or pop the current one if there isn't one
+	State (Option<GameState>),
+	/// Swap the current state for the given one, leaving the states below
+	/// it paused
+	Set (GameState),
+	/// Unwind the whole state stack, ending up in the given state
+	Reset (GameState),
+	Warp (Warp),
+}
+
 // Systems
 // =========================================================================
 
@@ -33,6 +46,7 @@ fn fadeout (
 	mut commands : Commands,
 	mut query : Query<(Entity, &mut ScreenFade, &mut BackgroundColor)>,
 	mut state : ResMut<State<GameState>>,
+	mut warp_events : EventWriter<WarpEvent>,
 	time : Res<Time>,
 ) {
 	for (id, mut fade, mut fill) in query.iter_mut() {
@@ -42,10 +56,12 @@ fn fadeout (
 		fill.0.set_a(fade.alpha);
 
 		if fade.timer.percent() > 0.5 && !fade.sent {
-			if let Some(next) = fade.next_state {
-				state.push(next).unwrap();
-			} else {
-				state.pop().unwrap();
+			match &fade.action {
+				FadeAction::State(Some(next)) => state.push(*next).unwrap(),
+				FadeAction::State(None) => state.pop().unwrap(),
+				FadeAction::Set(next) => state.set(*next).unwrap(),
+				FadeAction::Reset(next) => state.replace(*next).unwrap(),
+				FadeAction::Warp(warp) => warp_events.send(WarpEvent(warp.clone())),
 			}
 			fade.sent = true;
 		}
@@ -62,6 +78,43 @@ fn fadeout (
 pub fn create_fadeout (
 	commands : &mut Commands,
 	next_state : Option<GameState>,
+) {
+	spawn_fade(commands, FadeAction::State(next_state));
+}
+
+/// Fades out and swaps the current state for `next_state`, leaving the
+/// states below it paused
+pub fn create_set_fadeout (
+	commands : &mut Commands,
+	next_state : GameState,
+) {
+	spawn_fade(commands, FadeAction::Set(next_state));
+}
+
+/// Fades out and leaves every state on the stack, ending up in `next_state`
+/// alone
+pub fn create_reset_fadeout (
+	commands : &mut Commands,
+	next_state : GameState,
+) {
+	spawn_fade(commands, FadeAction::Reset(next_state));
+}
+
+/// Fades out, warps to another map, and fades back in without touching the
+/// game state
+pub fn create_warp_fadeout (
+	commands : &mut Commands,
+	warp : Warp,
+) {
+	spawn_fade(commands, FadeAction::Warp(warp));
+}
+
+// Helpers
+// =========================================================================
+
+fn spawn_fade (
+	commands : &mut Commands,
+	action : FadeAction,
 ) {
 	let mut color = Color::hex("432E3B").unwrap();
 	color.set_a(0.0);
@@ -80,7 +133,7 @@ pub fn create_fadeout (
 		.insert(ScreenFade {
 			alpha: 0.,
 			sent: false,
-			next_state,
+			action,
 			timer: Timer::from_seconds(1., TimerMode::Once),
 		})
 		.insert(Name::new("Fadeout"))
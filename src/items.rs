@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, HashMap};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use crate::combat::skills::{Effect, SkillTarget};
+use crate::combat::status::StatusEffect;
+use crate::core::data::AddRonAsset;
+
+/// What the player carries at the start of the game
+const STARTING_ITEMS : &[(&str, u32)] = &[("herb", 3), ("potion", 1)];
+
+const STARTING_GOLD : usize = 20;
+
+// Plugin
+// =========================================================================
+
+pub struct ItemsPlugin;
+
+impl Plugin for ItemsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_ron_asset::<ItemDatabase>(&["items.ron"])
+			.init_resource::<Inventory>()
+			.add_startup_system(load_items)
+		;
+	}
+}
+
+// Assets
+// =========================================================================
+
+/// Every item the player can carry, keyed by id. Loaded from `*.items.ron`
+/// files.
+///
+/// ```ron
+/// {
+///     "herb": (
+///         name: "Herb",
+///         effect: Heal,
+///         power: 5,
+///         target: Ally,
+///     ),
+/// }
+/// ```
+#[derive(TypeUuid, Deserialize, Debug, Default)]
+#[uuid = "8f2a6c41-3d7e-4b09-9e15-a4c0d6b3e872"]
+#[serde(transparent)]
+pub struct ItemDatabase (pub HashMap<String, ItemDef>);
+
+/// An item used up in battle. Items work like skills that cost the item
+/// instead of MP.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ItemDef {
+	pub name : String,
+	pub effect : Effect,
+	/// Damage dealt, or health or MP restored
+	pub power : isize,
+	pub target : SkillTarget,
+	/// Left on each target that's still standing afterwards
+	#[serde(default)]
+	pub status : Option<StatusEffect>,
+}
+
+impl ItemDatabase {
+	/// The display name of an item, falling back on its id
+	pub fn name<'a> (&'a self, id : &'a str) -> &'a str {
+		self.0.get(id).map_or(id, |def| def.name.as_str())
+	}
+}
+
+// Resources
+// =========================================================================
+
+#[derive(Resource)]
+pub struct Items (pub Handle<ItemDatabase>);
+
+/// The player's gold, and how many of each item they are carrying by item
+/// id
+#[derive(Resource, Debug, Clone)]
+pub struct Inventory {
+	pub gold : usize,
+	pub items : BTreeMap<String, u32>,
+}
+
+impl Default for Inventory {
+	fn default () -> Self {
+		Inventory {
+			gold: STARTING_GOLD,
+			items: STARTING_ITEMS
+				.iter()
+				.map(|(id, count)| (id.to_string(), *count))
+				.collect(),
+		}
+	}
+}
+
+impl Inventory {
+	pub fn add (&mut self, id : &str, count : u32) {
+		*self.items.entry(id.to_string()).or_default() += count;
+	}
+
+	/// Uses up one of an item. Returns false if there are none left.
+	pub fn take (&mut self, id : &str) -> bool {
+		let Some(count) = self.items.get_mut(id) else { return false };
+		*count -= 1;
+		if *count == 0 { self.items.remove(id); }
+		true
+	}
+}
+
+// Systems
+// =========================================================================
+
+fn load_items (
+	mut commands : Commands,
+	assets : Res<AssetServer>,
+) {
+	commands.insert_resource(Items(assets.load("data/base.items.ron")));
+}
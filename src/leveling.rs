@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::combat::{BaseStats, CombatStats};
+
+/// The highest level a character can reach
+pub const MAX_LEVEL : u32 = 50;
+
+// Plugin
+// =========================================================================
+
+pub struct LevelingPlugin;
+
+impl Plugin for LevelingPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_event::<LevelUpEvent>()
+		;
+	}
+}
+
+// Data
+// =========================================================================
+
+/// How a character gets stronger as they level up
+///
+/// ```ron
+/// (
+///     curve: (base: 20, exponent: 1.5),
+///     stats: (health: 3, attack: 0.5, defence: 0.5, speed: 0.25, mp: 1),
+/// )
+/// ```
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Growth {
+	#[serde(default)]
+	pub curve : XpCurve,
+	#[serde(default)]
+	pub stats : StatGrowth,
+}
+
+/// How much XP each level takes. Reaching level `n` takes
+/// `base * (n - 1) ^ exponent` XP in total.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct XpCurve {
+	pub base : f32,
+	pub exponent : f32,
+}
+
+impl Default for XpCurve {
+	fn default () -> Self {
+		XpCurve { base: 20., exponent: 1.5 }
+	}
+}
+
+impl XpCurve {
+	/// Total XP needed to reach `level`, starting from nothing at level 1
+	pub fn total_for (&self, level : u32) -> usize {
+		let levels = level.saturating_sub(1) as f32;
+		(self.base * levels.powf(self.exponent)).round() as usize
+	}
+
+	/// The level reached with `xp` in total
+	pub fn level_at (&self, xp : usize) -> u32 {
+		let mut level = 1;
+		while level < MAX_LEVEL && xp >= self.total_for(level + 1) { level += 1; }
+		level
+	}
+}
+
+/// Stats gained each level. Fractions build up over several levels, so
+/// `0.5` is a point every other level.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct StatGrowth {
+	#[serde(default)]
+	pub health : f32,
+	#[serde(default)]
+	pub attack : f32,
+	#[serde(default)]
+	pub defence : f32,
+	#[serde(default)]
+	pub speed : f32,
+	#[serde(default)]
+	pub mp : f32,
+}
+
+impl StatGrowth {
+	/// A character's stats at `level`, from their level 1 stats
+	pub fn at_level (&self, base : &BaseStats, level : u32) -> BaseStats {
+		let levels = level.saturating_sub(1) as f32;
+		let grow = |stat : isize, growth : f32| stat + (growth * levels) as isize;
+
+		BaseStats {
+			health: grow(base.health, self.health),
+			attack: grow(base.attack, self.attack),
+			defence: grow(base.defence, self.defence),
+			speed: grow(base.speed, self.speed),
+			mp: grow(base.mp, self.mp),
+		}
+	}
+}
+
+/// A party member going up one or more levels at once
+#[derive(Debug, Clone)]
+pub struct LevelUp {
+	pub name : String,
+	/// The level they went up to
+	pub level : u32,
+	/// How much each stat went up by
+	pub gains : BaseStats,
+}
+
+impl LevelUp {
+	/// The stats that went up, like "HP +3  Attack +1"
+	pub fn describe_gains (&self) -> String {
+		let gains = &self.gains;
+
+		[
+			("HP", gains.health),
+			("MP", gains.mp),
+			("Attack", gains.attack),
+			("Defence", gains.defence),
+			("Speed", gains.speed),
+		]
+			.iter()
+			.filter(|(_, gain)| *gain > 0)
+			.map(|(stat, gain)| format!("{stat} +{gain}"))
+			.collect::<Vec<_>>()
+			.join("  ")
+	}
+}
+
+/// Raises `stats` to what they are at a new level, keeping any health and
+/// MP already lost. Returns how much each stat went up by.
+pub fn grow_stats (stats : &mut CombatStats, grown : &BaseStats) -> BaseStats {
+	let gains = BaseStats {
+		health: grown.health - stats.max_health,
+		attack: grown.attack - stats.attack,
+		defence: grown.defence - stats.defence,
+		speed: grown.speed - stats.speed,
+		mp: grown.mp - stats.max_mp,
+	};
+
+	apply_gains(stats, &gains);
+	gains
+}
+
+/// Adds stats gained from levelling up on to `stats`
+pub fn apply_gains (stats : &mut CombatStats, gains : &BaseStats) {
+	stats.max_health += gains.health;
+	stats.health += gains.health;
+	stats.attack += gains.attack;
+	stats.defence += gains.defence;
+	stats.speed += gains.speed;
+	stats.max_mp += gains.mp;
+	stats.mp += gains.mp;
+}
+
+// Events
+// =========================================================================
+
+/// Sent when level ups are shown to the player, for the fanfare
+pub struct LevelUpEvent;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BASE : BaseStats = BaseStats { health: 15, attack: 2, defence: 1, speed: 6, mp: 6 };
+
+	const GROWTH : StatGrowth = StatGrowth {
+		health: 3.,
+		attack: 0.5,
+		defence: 0.5,
+		speed: 0.25,
+		mp: 1.,
+	};
+
+	// Curve
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn level_1_takes_no_xp () {
+		assert_eq!(XpCurve::default().total_for(1), 0);
+		assert_eq!(XpCurve::default().total_for(0), 0);
+		assert_eq!(XpCurve::default().level_at(0), 1);
+	}
+
+	#[test]
+	fn each_level_takes_more_xp () {
+		for curve in [XpCurve::default(), XpCurve { base: 22., exponent: 1.5 }, XpCurve { base: 5., exponent: 1. }] {
+			for level in 1..MAX_LEVEL {
+				assert!(curve.total_for(level + 1) > curve.total_for(level), "{curve:?} at level {level}");
+			}
+		}
+	}
+
+	#[test]
+	fn level_at_the_total_for_each_level () {
+		let curve = XpCurve::default();
+
+		for level in 1..=MAX_LEVEL {
+			let xp = curve.total_for(level);
+			assert_eq!(curve.level_at(xp), level);
+
+			if level > 1 {
+				assert_eq!(curve.level_at(xp - 1), level - 1);
+			}
+		}
+	}
+
+	#[test]
+	fn levels_stop_at_the_max () {
+		let curve = XpCurve::default();
+
+		assert_eq!(curve.level_at(curve.total_for(MAX_LEVEL + 10)), MAX_LEVEL);
+		assert_eq!(curve.level_at(usize::MAX), MAX_LEVEL);
+	}
+
+	// Stats
+	// -------------------------------------------------------------------------
+
+	#[test]
+	fn level_1_stats_are_the_base () {
+		assert_eq!(GROWTH.at_level(&BASE, 1), BASE);
+	}
+
+	#[test]
+	fn fractions_build_up_over_levels () {
+		assert_eq!(
+			GROWTH.at_level(&BASE, 5),
+			BaseStats { health: 27, attack: 4, defence: 3, speed: 7, mp: 10 },
+		);
+		// A quarter point a level isn't a point until the fourth level
+		assert_eq!(GROWTH.at_level(&BASE, 4).speed, 6);
+	}
+
+	#[test]
+	fn one_award_can_go_up_several_levels () {
+		let curve = XpCurve::default();
+		let mut stats = CombatStats::from(BASE);
+
+		let level = curve.level_at(curve.total_for(4) + 1);
+		let gains = grow_stats(&mut stats, &GROWTH.at_level(&BASE, level));
+
+		assert_eq!(level, 4);
+		assert_eq!(gains, BaseStats { health: 9, attack: 1, defence: 1, speed: 0, mp: 3 });
+		assert_eq!((stats.max_health, stats.attack, stats.max_mp), (24, 3, 9));
+	}
+
+	#[test]
+	fn growing_keeps_damage_taken () {
+		let mut stats = CombatStats::from(BASE);
+		stats.health = 5;
+		stats.mp = 1;
+
+		let gains = grow_stats(&mut stats, &GROWTH.at_level(&BASE, 2));
+
+		assert_eq!((stats.health, stats.max_health), (5 + gains.health, 18));
+		assert_eq!((stats.mp, stats.max_mp), (1 + gains.mp, 7));
+	}
+
+	#[test]
+	fn gains_apply_on_top_of_equipment () {
+		let mut stats = CombatStats { attack: 5, ..CombatStats::from(BASE) };
+		apply_gains(&mut stats, &BaseStats { health: 3, attack: 1, defence: 0, speed: 0, mp: 1 });
+
+		assert_eq!(stats.attack, 6);
+		assert_eq!((stats.health, stats.max_health), (18, 18));
+		assert_eq!((stats.mp, stats.max_mp), (7, 7));
+	}
+}
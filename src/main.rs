@@ -6,10 +6,14 @@ mod player;
 mod combat;
 mod scenes;
 mod npc;
+mod party;
 mod util;
 mod core;
 mod ui;
 mod consts;
+mod items;
+mod leveling;
+mod save;
 
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
@@ -19,8 +23,14 @@ use crate::consts::BG_COLOR;
 use crate::core::assets::AssetsPlugin;
 use crate::core::audio::AudioPlugin;
 use crate::core::debug::DebugPlugin;
+use crate::core::settings::Settings;
+use crate::core::tilemap::TilemapPlugin;
+use crate::core::tilemap::validate::validate_all_maps;
 use crate::core::transition::TransitionPlugin;
+use crate::items::ItemsPlugin;
+use crate::leveling::LevelingPlugin;
 use crate::npc::NpcPlugin;
+use crate::party::PartyPlugin;
 use crate::player::PlayerPlugin;
 use crate::scenes::ScenesPlugin;
 use crate::ui::UiPlugin;
@@ -33,16 +43,27 @@ pub enum GameState {
     MainMenu,
     Overworld,
     Combat,
+    GameOver,
 }
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--validate-maps") {
+        let passed = validate_all_maps();
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     App::new()
         .add_state(GameState::MainMenu)
         .insert_resource(ClearColor(BG_COLOR))
         .insert_resource(Msaa { samples: 1 })
+        .init_resource::<Settings>()
         .add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
+                .set(AssetPlugin {
+                    watch_for_changes: true,
+                    ..default()
+                })
                 .set(WindowPlugin {
                     window: WindowDescriptor {
                         title: "Bevpg".to_string(),
@@ -54,8 +75,12 @@ fn main() {
         )
         .add_plugin(DebugPlugin)
         .add_plugin(AssetsPlugin)
+        .add_plugin(TilemapPlugin)
         .add_plugin(UiPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(PartyPlugin)
+        .add_plugin(ItemsPlugin)
+        .add_plugin(LevelingPlugin)
         .add_plugin(NpcPlugin)
         .add_plugin(CombatPlugin)
         .add_plugin(TransitionPlugin)
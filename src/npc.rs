@@ -1,8 +1,12 @@
+use std::str::FromStr;
 use bevy::prelude::*;
 use crate::{GameState, TILE_SIZE};
-use crate::combat::CombatStats;
 use crate::core::assets::{PixelFont, spawn_tilesheet_sprite, Tilesheet};
+use crate::core::tilemap::ActiveMap;
+use crate::items::Inventory;
+use crate::party::{CharacterDatabase, Characters, Party};
 use crate::player::Player;
+use crate::save::SaveData;
 
 // Plugin
 // =========================================================================
@@ -21,6 +25,10 @@ impl Plugin for NpcPlugin {
 					.with_system(npc_dialog)
 					.with_system(highlight_npc)
 			)
+			.add_system_set(
+				SystemSet::on_exit(GameState::Overworld)
+					.with_system(despawn_dialog_ui)
+			)
 		;
 	}
 }
@@ -37,9 +45,25 @@ pub struct NpcDialogUIText;
 #[derive(Component)]
 pub struct NpcBubble;
 
-#[derive(Component)]
+#[derive(Component, Debug, Clone)]
 pub enum Npc {
+	/// Heals the party and saves the game
 	Healer,
+	/// A character who joins the party when spoken to, by their id in the
+	/// character database. Written as `recruit:<id>`.
+	Recruit (String),
+}
+
+impl FromStr for Npc {
+	type Err = String;
+
+	fn from_str(s : &str) -> Result<Self, Self::Err> {
+		match s.split_once(':') {
+			None if s == "healer" => Ok(Npc::Healer),
+			Some(("recruit", id)) if !id.is_empty() => Ok(Npc::Recruit(id.to_string())),
+			_ => Err(format!("unknown npc '{s}'")),
+		}
+	}
 }
 
 // Systems
@@ -125,36 +149,87 @@ fn setup_dialog_ui (
 	});
 }
 
+fn despawn_dialog_ui (
+	mut commands : Commands,
+	query : Query<Entity, Or<(With<NpcDialogUIRoot>, With<NpcBubble>)>>,
+) {
+	for id in &query {
+		commands.entity(id).despawn_recursive();
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
 fn npc_dialog (
-	mut player_query : Query<(&mut Player, &Transform, &mut CombatStats)>,
+	mut commands : Commands,
+	mut player_query : Query<(&mut Player, &Transform)>,
 	mut ui : Query<&mut Visibility, With<NpcDialogUIRoot>>,
 	mut ui_text : Query<&mut Text, With<NpcDialogUIText>>,
 	npc_query : Query<(&Npc, &Transform)>,
+	mut party : ResMut<Party>,
+	inventory : Res<Inventory>,
+	active_map : Option<Res<ActiveMap>>,
+	characters : Res<Characters>,
+	databases : Res<Assets<CharacterDatabase>>,
 	keyboard : Res<Input<KeyCode>>,
 ) {
-	let (mut player, player_transform, mut stats) = player_query.single_mut();
+	let (mut player, player_transform) = player_query.single_mut();
+	let mut dialog = ui.single_mut();
 
-	if !player.active {
+	// The player is also held still during warps and while being placed on
+	// a map, so only an open dialog is closed here
+	if dialog.is_visible {
 		if keyboard.any_just_pressed([KeyCode::Space, KeyCode::E]) {
-			ui.single_mut().is_visible = false;
+			dialog.is_visible = false;
 			player.active = true;
 		}
 
 		return;
 	}
 
-	if !keyboard.just_pressed(KeyCode::E) { return; }
+	if !player.active || !keyboard.just_pressed(KeyCode::E) { return; }
 
-	for _npc in npc_query.iter().filter(|(_, transform)| {
+	for (npc, _) in npc_query.iter().filter(|(_, transform)| {
 		Vec2::distance(
 			transform.translation.truncate(),
 			player_transform.translation.truncate(),
 		) < TILE_SIZE * 1.25
 	}) {
-		ui_text.single_mut().sections[0].value = "Heal, heal, HEAL!".into();
-		ui.single_mut().is_visible = true;
+		let message = match npc {
+			Npc::Healer => {
+				party.heal();
+
+				let Some(active_map) = &active_map else { continue };
+				commands.insert_resource(SaveData {
+					party: party.clone(),
+					inventory: inventory.clone(),
+					map: active_map.0.clone(),
+					position: player_transform.translation,
+				});
+
+				"Heal, heal, HEAL! Your progress is saved.".to_string()
+			}
+			Npc::Recruit(id) => {
+				let Some(def) = databases.get(&characters.0).and_then(|db| db.0.get(id)) else {
+					warn!("Recruit is an unknown character '{id}'");
+					continue;
+				};
+
+				if party.contains(id) {
+					format!("{}: Lead the way!", def.name)
+				} else if party.join(id, def) {
+					match &def.equipment.weapon {
+						Some(weapon) => format!("{} joins the party, {} in hand!", def.name, weapon.name),
+						None => format!("{} joins the party!", def.name),
+					}
+				} else {
+					format!("{}: Your party looks full.", def.name)
+				}
+			}
+		};
+
+		ui_text.single_mut().sections[0].value = message;
+		dialog.is_visible = true;
 		player.active = false;
-		stats.health = stats.max_health;
 	}
 }
 
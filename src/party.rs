@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use crate::combat::{BaseStats, CombatStats};
+use crate::combat::status::{Status, StatusEffect};
+use crate::core::data::AddRonAsset;
+use crate::core::tilemap::grid::translation_to_cell;
+use crate::GameState;
+use crate::leveling::{Growth, grow_stats, LevelUp};
+use crate::player::Player;
+
+/// The most characters that can be in the party at once
+pub const MAX_PARTY_SIZE : usize = 4;
+
+/// Who the party starts out with
+const STARTING_PARTY : &[&str] = &["hero"];
+
+/// How many tiles the party walks for each tick of a lingering status
+const STEPS_PER_STATUS_TICK : u32 = 4;
+
+// Plugin
+// =========================================================================
+
+pub struct PartyPlugin;
+
+impl Plugin for PartyPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_ron_asset::<CharacterDatabase>(&["characters.ron"])
+			.init_resource::<Party>()
+			.add_startup_system(load_characters)
+			.add_system_set(
+				SystemSet::on_update(GameState::Overworld)
+					.with_system(form_starting_party)
+					.with_system(tick_lingering_statuses)
+			)
+		;
+	}
+}
+
+// Assets
+// =========================================================================
+
+/// Every character that can join the party, keyed by id. Loaded from
+/// `*.characters.ron` files.
+///
+/// ```ron
+/// {
+///     "hero": (
+///         name: "Hero",
+///         sprite: 25,
+///         stats: (health: 15, attack: 2, defence: 1, speed: 6, mp: 6),
+///         growth: (
+///             curve: (base: 20, exponent: 1.5),
+///             stats: (health: 3, attack: 0.5, defence: 0.5, speed: 0.25, mp: 1),
+///         ),
+///         equipment: (
+///             weapon: Some((name: "Wooden Sword", attack: 1)),
+///         ),
+///         skills: ["fire", "cure"],
+///     ),
+/// }
+/// ```
+#[derive(TypeUuid, Deserialize, Debug, Default)]
+#[uuid = "5e0d7b2a-94c3-4f61-a8b5-3c17e9d2f046"]
+#[serde(transparent)]
+pub struct CharacterDatabase (pub HashMap<String, CharacterDef>);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CharacterDef {
+	pub name : String,
+	/// Tilesheet index of the character's sprite
+	pub sprite : usize,
+	/// Stats at level 1
+	pub stats : BaseStats,
+	/// The level the character joins the party at
+	#[serde(default = "first_level")]
+	pub level : u32,
+	#[serde(default)]
+	pub growth : Growth,
+	#[serde(default)]
+	pub equipment : Equipment,
+	/// Skill ids from the skill database
+	#[serde(default)]
+	pub skills : Vec<String>,
+}
+
+/// What a character has equipped. Each piece adds its bonuses on top of
+/// the character's own stats in combat.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Equipment {
+	#[serde(default)]
+	pub weapon : Option<Gear>,
+	#[serde(default)]
+	pub armour : Option<Gear>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Gear {
+	pub name : String,
+	#[serde(default)]
+	pub attack : isize,
+	#[serde(default)]
+	pub defence : isize,
+}
+
+impl Equipment {
+	fn pieces (&self) -> impl Iterator<Item = &Gear> {
+		self.weapon.iter().chain(self.armour.iter())
+	}
+
+	pub fn attack (&self) -> isize {
+		self.pieces().map(|gear| gear.attack).sum()
+	}
+
+	pub fn defence (&self) -> isize {
+		self.pieces().map(|gear| gear.defence).sum()
+	}
+}
+
+// Resources
+// =========================================================================
+
+#[derive(Resource)]
+pub struct Characters (pub Handle<CharacterDatabase>);
+
+/// The characters travelling with the player, in battle order. Their health
+/// carries over from one battle to the next.
+#[derive(Resource, Default, Clone)]
+pub struct Party {
+	pub members : Vec<PartyMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PartyMember {
+	/// The character's id in the character database
+	pub id : String,
+	pub name : String,
+	pub sprite : usize,
+	pub level : u32,
+	/// Total XP earned, including what it took to reach their current level
+	pub xp : usize,
+	/// The member's own stats, without their equipment
+	pub stats : CombatStats,
+	/// The member's stats at level 1, which their level's growth is added to
+	pub base : BaseStats,
+	pub growth : Growth,
+	pub equipment : Equipment,
+	pub skills : Vec<String>,
+	/// Statuses that lingered after the last battle
+	pub statuses : Vec<StatusEffect>,
+}
+
+impl Party {
+	pub fn is_full (&self) -> bool {
+		self.members.len() >= MAX_PARTY_SIZE
+	}
+
+	pub fn contains (&self, id : &str) -> bool {
+		self.members.iter().any(|member| member.id == id)
+	}
+
+	/// Adds a character to the end of the party. Returns false if the party
+	/// is full or the character is already in it.
+	pub fn join (&mut self, id : &str, def : &CharacterDef) -> bool {
+		if self.is_full() || self.contains(id) { return false; }
+
+		let level = def.level.max(1);
+
+		self.members.push(PartyMember {
+			id: id.to_string(),
+			name: def.name.clone(),
+			sprite: def.sprite,
+			level,
+			xp: def.growth.curve.total_for(level),
+			stats: def.growth.stats.at_level(&def.stats, level).into(),
+			base: def.stats,
+			growth: def.growth.clone(),
+			equipment: def.equipment.clone(),
+			skills: def.skills.clone(),
+			statuses: Vec::new(),
+		});
+
+		true
+	}
+
+	/// Restores every member to full health and MP, and cures their
+	/// statuses
+	pub fn heal (&mut self) {
+		for member in &mut self.members {
+			member.stats.health = member.stats.max_health;
+			member.stats.mp = member.stats.max_mp;
+			member.statuses.clear();
+		}
+	}
+}
+
+impl PartyMember {
+	/// Adds XP, going up as many levels as it's enough for. Returns the
+	/// level up if there was one.
+	pub fn gain_xp (&mut self, xp : usize) -> Option<LevelUp> {
+		self.xp += xp;
+
+		let level = self.growth.curve.level_at(self.xp);
+		if level <= self.level { return None; }
+		self.level = level;
+
+		let grown = self.growth.stats.at_level(&self.base, level);
+		Some(LevelUp {
+			name: self.name.clone(),
+			level,
+			gains: grow_stats(&mut self.stats, &grown),
+		})
+	}
+
+	/// The member's stats in battle, with their equipment on
+	pub fn combat_stats (&self) -> CombatStats {
+		CombatStats {
+			attack: self.stats.attack + self.equipment.attack(),
+			defence: self.stats.defence + self.equipment.defence(),
+			..self.stats.clone()
+		}
+	}
+}
+
+// Systems
+// =========================================================================
+
+fn load_characters (
+	mut commands : Commands,
+	assets : Res<AssetServer>,
+) {
+	commands.insert_resource(Characters(assets.load("data/base.characters.ron")));
+}
+
+/// Fills an empty party with the starting characters, for a new game
+fn form_starting_party (
+	mut party : ResMut<Party>,
+	characters : Res<Characters>,
+	databases : Res<Assets<CharacterDatabase>>,
+) {
+	if !party.members.is_empty() { return; }
+	let Some(database) = databases.get(&characters.0) else { return };
+
+	for id in STARTING_PARTY {
+		match database.0.get(*id) {
+			Some(def) => { party.join(id, def); }
+			None => error!("Starting party has an unknown character '{id}'"),
+		}
+	}
+}
+
+/// Keeps poison ticking as the party walks around after a battle. It never
+/// takes anyone below 1 HP outside of battle.
+fn tick_lingering_statuses (
+	mut party : ResMut<Party>,
+	player_query : Query<(&Player, &Transform)>,
+	mut last_cell : Local<Option<IVec2>>,
+	mut steps : Local<u32>,
+) {
+	let Ok((player, transform)) = player_query.get_single() else { return };
+	let cell = translation_to_cell(transform.translation);
+
+	let stepped = last_cell.is_some_and(|last| last != cell);
+	*last_cell = Some(cell);
+
+	if !player.active || !stepped { return; }
+
+	*steps += 1;
+	if *steps < STEPS_PER_STATUS_TICK { return; }
+	*steps = 0;
+
+	for member in &mut party.members {
+		for effect in &mut member.statuses {
+			if let Status::Poison(amount) = effect.status {
+				member.stats.health = (member.stats.health - amount).max(1);
+			}
+
+			effect.turns = effect.turns.saturating_sub(1);
+		}
+
+		member.statuses.retain(|effect| effect.turns > 0);
+	}
+}
+
+// Helpers
+// =========================================================================
+
+fn first_level () -> u32 {
+	1
+}
@@ -1,13 +1,14 @@
 use std::time::Duration;
 use bevy::prelude::*;
-use bevy::sprite::collide_aabb::collide;
 use rand::Rng;
 use crate::{GameState, PIXEL_SIZE, TILE_SIZE};
-use crate::combat::CombatStats;
+use crate::combat::damage::CombatRng;
+use crate::combat::encounters::{EncounterTables, Encounters};
 use crate::core::animator;
 use crate::core::assets::{spawn_tilesheet_sprite, Tilesheet};
-use crate::core::tilemap::{EncounterSpawner, TileCollider};
-use crate::core::transition::create_fadeout;
+use crate::core::tilemap::{DEFAULT_SPAWN, Map, SpawnPoint};
+use crate::core::tilemap::grid::{MapGrid, translation_to_cell};
+use crate::core::transition::{create_fadeout, create_warp_fadeout};
 
 // Plugin
 // =========================================================================
@@ -30,11 +31,17 @@ impl Plugin for PlayerPlugin {
 					.with_system(player_movement)
 					.with_system(cam_follow_player.after(player_movement))
 					.with_system(player_encounter_checker.after(player_movement))
+					.with_system(player_warp_checker.after(player_movement))
+					.with_system(place_player_at_spawn)
 			)
 			.add_system_set(
 				SystemSet::on_pause(GameState::Overworld)
 					.with_system(hide_player)
 			)
+			.add_system_set(
+				SystemSet::on_exit(GameState::Overworld)
+					.with_system(despawn_player)
+			)
 		;
 	}
 }
@@ -49,12 +56,19 @@ pub struct Player {
 	speed : f32,
 	just_moved : bool,
 	walk_cycle: Handle<AnimationClip>,
-	pub xp : usize,
 }
 
 #[derive(Component)]
 pub struct EncounterTimer (Timer);
 
+// Resources
+// =========================================================================
+
+/// The spawn point the player should be moved to once the active map has
+/// been spawned
+#[derive(Resource)]
+pub struct PendingSpawn (pub String);
+
 // Systems
 // =========================================================================
 
@@ -82,34 +96,38 @@ fn spawn_player (
 
 	commands.entity(player_sprite_id).insert(player_sprite_name);
 
+	// The player is placed once the map has loaded
+	move_player_to_spawn(&mut commands, DEFAULT_SPAWN);
+
 	commands
 		.spawn((
 			player_name,
 			AnimationPlayer::default(),
-			Transform {
-				translation: Vec3::new(2. * TILE_SIZE, -2. * TILE_SIZE, 900.),
-				..default()
-			},
+			Transform::from_xyz(0., 0., 900.),
 			GlobalTransform::default(),
 			Visibility::default(),
 			ComputedVisibility::default(),
 			Player {
-				active: true,
+				active: false,
 				speed: 4.,
 				just_moved: false,
 				walk_cycle: walk_cycle_handle,
-				xp: 0,
 			},
 			EncounterTimer(Timer::from_seconds(1.0, TimerMode::Repeating)),
-			CombatStats {
-				max_health: 15,
-				health: 15,
-				attack: 3,
-				defence: 1,
-			},
 		)).push_children(&[player_sprite_id]);
 }
 
+fn despawn_player (
+	mut commands : Commands,
+	query : Query<Entity, With<Player>>,
+) {
+	for id in &query {
+		commands.entity(id).despawn_recursive();
+	}
+
+	commands.remove_resource::<PendingSpawn>();
+}
+
 fn show_player (mut query : Query<(&mut Visibility, &mut Player)>) {
 	let (mut visibility, mut player) = query.single_mut();
 	visibility.is_visible = true;
@@ -122,7 +140,7 @@ fn hide_player (mut query : Query<&mut Visibility, With<Player>>) {
 
 fn player_movement (
 	mut player_query : Query<(&mut Player, &mut Transform, &mut AnimationPlayer)>,
-	wall_query : Query<&Transform, (With<TileCollider>, Without<Player>)>,
+	grid : Option<Res<MapGrid>>,
 	keyboard : Res<Input<KeyCode>>,
 	time : Res<Time>,
 ) {
@@ -146,17 +164,15 @@ fn player_movement (
 	let dist = player.speed * TILE_SIZE * time.delta_seconds();
 	let prev = transform.translation;
 
+	let blocked = |target : Vec3| grid.as_ref().is_some_and(|grid| {
+		grid.any_overlapping(target, player_half_size(), |cell| cell.collider)
+	});
+
 	let target = transform.translation + Vec3::new(delta_x * dist, 0., 0.);
-	if !wall_query
-		.iter()
-		.any(|&transform| tile_collision_check(target, transform.translation))
-	{ transform.translation = target; }
+	if !blocked(target) { transform.translation = target; }
 
 	let target = transform.translation + Vec3::new(0., delta_y * dist, 0.);
-	if !wall_query
-		.iter()
-		.any(|&transform| tile_collision_check(target, transform.translation))
-	{ transform.translation = target; }
+	if !blocked(target) { transform.translation = target; }
 
 	transform.translation.z = prev.z;
 	player.just_moved = transform.translation.x != prev.x || transform.translation.y != prev.y;
@@ -172,7 +188,10 @@ fn player_movement (
 fn player_encounter_checker (
 	mut commands : Commands,
 	mut player_query : Query<(&mut Player, &Transform, &mut EncounterTimer), With<Player>>,
-	encounter_query : Query<&Transform, (With<EncounterSpawner>, Without<Player>)>,
+	grid : Option<Res<MapGrid>>,
+	encounters : Res<Encounters>,
+	tables : Res<Assets<EncounterTables>>,
+	mut rng : ResMut<CombatRng>,
 	time : Res<Time>,
 ) {
 	let (mut player, player_transform, mut encounter_timer) = player_query.single_mut();
@@ -180,18 +199,23 @@ fn player_encounter_checker (
 
 	if !player.just_moved { return; }
 
-	if !encounter_query
-		.iter()
-		.any(|&transform| tile_collision_check(player_pos, transform.translation))
-	{ return; }
+	let Some(grid) = grid else { return };
+	let Some(table) = grid.encounter_overlapping(player_pos, player_half_size()) else { return };
 
 	encounter_timer.0.tick(time.delta());
 	if !encounter_timer.0.just_finished() { return; }
 
 	encounter_timer.0.set_duration(
-		Duration::from_millis(rand::thread_rng().gen_range(1000..=3000))
+		Duration::from_millis(rng.0.gen_range(1000..=3000))
 	);
 
+	let Some(encounter) = tables.get(&encounters.0).and_then(|tables| tables.roll(table, &mut rng.0)) else {
+		warn!("No encounter could be rolled from table '{table}'");
+		return;
+	};
+
+	commands.insert_resource(encounter);
+
 	player.active = false;
 	create_fadeout(
 		&mut commands,
@@ -199,6 +223,58 @@ fn player_encounter_checker (
 	);
 }
 
+fn player_warp_checker (
+	mut commands : Commands,
+	mut player_query : Query<(&mut Player, &Transform)>,
+	grid : Option<Res<MapGrid>>,
+	mut last_cell : Local<Option<IVec2>>,
+) {
+	let (mut player, transform) = player_query.single_mut();
+	let cell = translation_to_cell(transform.translation);
+
+	// Only warp when stepping onto a warp, so arriving on one doesn't
+	// immediately send the player back
+	let entered = *last_cell != Some(cell);
+	*last_cell = Some(cell);
+
+	if !player.active || !player.just_moved || !entered { return; }
+
+	let Some(warp) = grid.as_ref().and_then(|grid| grid.warp(cell)) else { return };
+
+	player.active = false;
+	create_warp_fadeout(&mut commands, warp.clone());
+}
+
+fn place_player_at_spawn (
+	mut commands : Commands,
+	pending : Option<Res<PendingSpawn>>,
+	map_query : Query<(), With<Map>>,
+	spawn_query : Query<(&SpawnPoint, &Transform), Without<Player>>,
+	mut player_query : Query<(&mut Player, &mut Transform)>,
+) {
+	let Some(pending) = pending else { return };
+	if map_query.is_empty() { return; }
+
+	let (mut player, mut transform) = player_query.single_mut();
+	let find = |name : &str| spawn_query
+		.iter()
+		.find(|(spawn, _)| spawn.0 == name)
+		.map(|(_, transform)| transform.translation);
+
+	let spawn = find(&pending.0).or_else(|| {
+		warn!("Map has no spawn point named '{}'", pending.0);
+		find(DEFAULT_SPAWN)
+	});
+
+	if let Some(spawn) = spawn {
+		transform.translation.x = spawn.x;
+		transform.translation.y = spawn.y;
+	}
+
+	player.active = true;
+	commands.remove_resource::<PendingSpawn>();
+}
+
 fn cam_follow_player (
 	mut camera_query : Query<&mut Transform, (With<Camera>, Without<Player>)>,
 	player_query : Query<&Transform, (With<Player>, Without<Camera>)>,
@@ -210,17 +286,22 @@ fn cam_follow_player (
 	camera_transform.translation.y = player_transform.translation.y;
 }
 
+// Utilities
+// =========================================================================
+
+/// Moves the player to the named spawn point on the active map, waiting
+/// for the map to be spawned first if it is still loading. Falls back to
+/// the map's default spawn if there is no spawn point with that name.
+pub fn move_player_to_spawn (
+	commands : &mut Commands,
+	spawn : &str,
+) {
+	commands.insert_resource(PendingSpawn(spawn.to_string()));
+}
+
 // Helpers
 // =========================================================================
 
-fn tile_collision_check(
-	target_player_pos : Vec3,
-	tile_translation: Vec3,
-) -> bool {
-	collide(
-		target_player_pos,
-		Vec2::splat(TILE_SIZE - PIXEL_SIZE),
-		tile_translation,
-		Vec2::splat(TILE_SIZE)
-	).is_some()
+fn player_half_size () -> Vec2 {
+	Vec2::splat((TILE_SIZE - PIXEL_SIZE) * 0.5)
 }
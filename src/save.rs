@@ -0,0 +1,21 @@
+use bevy::prelude::*;
+use crate::core::tilemap::MapAsset;
+use crate::items::Inventory;
+use crate::party::Party;
+
+// Resources
+// =========================================================================
+
+/// A snapshot of the game, taken whenever the party visits a healer. The
+/// game over screen loads it back. Saves only last while the game is
+/// running.
+#[derive(Resource, Clone)]
+pub struct SaveData {
+	pub party : Party,
+	pub inventory : Inventory,
+	/// The map the save was made on
+	pub map : Handle<MapAsset>,
+	/// Where the player stood, which is also where the party wakes up after
+	/// being defeated
+	pub position : Vec3,
+}
@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use crate::core::assets::PixelFont;
+use crate::core::tilemap::TeleportEvent;
+use crate::core::transition::{create_fadeout, create_reset_fadeout};
+use crate::GameState;
+use crate::items::Inventory;
+use crate::party::Party;
+use crate::save::SaveData;
+use crate::ui::Disabled;
+
+// Plugin
+// =========================================================================
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_system_set(
+				SystemSet::on_enter(GameState::GameOver)
+					.with_system(setup_game_over)
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::GameOver)
+					.with_system(on_load_click)
+					.with_system(on_title_click)
+			)
+			.add_system_set(
+				SystemSet::on_exit(GameState::GameOver)
+					.with_system(despawn_game_over)
+			)
+		;
+	}
+}
+
+// Components
+// =========================================================================
+
+#[derive(Component)]
+pub struct GameOverUIRoot;
+
+// Systems
+// =========================================================================
+
+fn setup_game_over (
+	mut commands : Commands,
+	pixel_font : Res<PixelFont>,
+	save : Option<Res<SaveData>>,
+) {
+	commands.spawn((
+		GameOverUIRoot,
+		NodeBundle {
+			style: Style {
+				size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+				..default()
+			},
+			..default()
+		},
+	)).with_children(|parent| {
+		parent.spawn(NodeBundle {
+			style: Style {
+				margin: UiRect::all(Val::Auto),
+				flex_direction: FlexDirection::Column,
+				align_items: AlignItems::Center,
+				..default()
+			},
+			..default()
+		}).with_children(|parent| {
+			parent.spawn(TextBundle::from_section(
+				"Game Over",
+				TextStyle {
+					font: pixel_font.0.clone(),
+					font_size: 80.,
+					color: Color::hex("D5543B").unwrap(),
+				},
+			).with_style(Style {
+				margin: UiRect::bottom(Val::Px(40.)),
+				..default()
+			}));
+
+			// Loading needs a healer to have been visited first
+			let mut load = parent.spawn((
+				ButtonBundle {
+					style: Style {
+						margin: UiRect::bottom(Val::Px(20.)),
+						padding: UiRect::new(
+							Val::Px(30.), Val::Px(30.),
+							Val::Px(10.), Val::Px(10.),
+						),
+						..default()
+					},
+					background_color: Color::hex(if save.is_some() { "6ED57E" } else { "6B6B6B" }).unwrap().into(),
+					..default()
+				},
+				Name::new("load"),
+			));
+
+			if save.is_none() { load.insert(Disabled); }
+
+			load.with_children(|parent| {
+				parent.spawn(TextBundle::from_section(
+					"Load last save",
+					TextStyle {
+						font: pixel_font.0.clone(),
+						font_size: 40.,
+						color: Color::WHITE,
+					},
+				));
+			});
+
+			parent.spawn((
+				ButtonBundle {
+					style: Style {
+						padding: UiRect::new(
+							Val::Px(30.), Val::Px(30.),
+							Val::Px(10.), Val::Px(10.),
+						),
+						..default()
+					},
+					background_color: Color::hex("EAB644").unwrap().into(),
+					..default()
+				},
+				Name::new("title"),
+			)).with_children(|parent| {
+				parent.spawn(TextBundle::from_section(
+					"Return to title",
+					TextStyle {
+						font: pixel_font.0.clone(),
+						font_size: 40.,
+						color: Color::WHITE,
+					},
+				));
+			});
+		});
+	});
+}
+
+/// Puts the party and inventory back how they were at the last save, and
+/// returns to the overworld where it was made
+fn on_load_click (
+	mut commands : Commands,
+	interaction_query : Query<(Entity, &Interaction, &Name), (Changed<Interaction>, Without<Disabled>)>,
+	save : Option<Res<SaveData>>,
+	mut party : ResMut<Party>,
+	mut inventory : ResMut<Inventory>,
+	mut teleports : EventWriter<TeleportEvent>,
+) {
+	let Some(save) = save else { return };
+
+	for (entity, interaction, name) in &interaction_query {
+		if interaction == &Interaction::Clicked && name.as_str() == "load" {
+			commands.entity(entity).insert(Disabled);
+
+			*party = save.party.clone();
+			*inventory = save.inventory.clone();
+
+			teleports.send(TeleportEvent {
+				map: save.map.clone(),
+				position: save.position,
+			});
+
+			// The overworld is still paused underneath
+			create_fadeout(
+				&mut commands,
+				None,
+			);
+		}
+	}
+}
+
+/// Throws away the current game and goes back to the main menu, where a new
+/// one can be started
+fn on_title_click (
+	mut commands : Commands,
+	interaction_query : Query<(Entity, &Interaction, &Name), (Changed<Interaction>, Without<Disabled>)>,
+	mut party : ResMut<Party>,
+	mut inventory : ResMut<Inventory>,
+) {
+	for (entity, interaction, name) in &interaction_query {
+		if interaction == &Interaction::Clicked && name.as_str() == "title" {
+			commands.entity(entity).insert(Disabled);
+			commands.remove_resource::<SaveData>();
+
+			*party = Party::default();
+			*inventory = Inventory::default();
+
+			create_reset_fadeout(
+				&mut commands,
+				GameState::MainMenu,
+			);
+		}
+	}
+}
+
+fn despawn_game_over (
+	mut commands : Commands,
+	query : Query<Entity, With<GameOverUIRoot>>,
+) {
+	for id in &query {
+		commands.entity(id).despawn_recursive();
+	}
+}
@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use crate::core::assets::PixelFont;
+use crate::core::settings::Settings;
 use crate::core::transition::create_fadeout;
 use crate::GameState;
 use crate::ui::Disabled;
@@ -23,11 +24,17 @@ impl Plugin for MainMenuPlugin {
 			.add_system_set(
 				SystemSet::on_update(GameState::MainMenu)
 					.with_system(on_start_click)
+					.with_system(on_battle_mode_click)
+					.with_system(on_defeat_mode_click)
 			)
 			.add_system_set(
 				SystemSet::on_pause(GameState::MainMenu)
 					.with_system(set_ui_visibility(false))
 			)
+			.add_system_set(
+				SystemSet::on_exit(GameState::MainMenu)
+					.with_system(despawn_menu)
+			)
 		;
 	}
 }
@@ -41,12 +48,19 @@ pub struct MainMenuUIRoot;
 #[derive(Component)]
 pub struct Active(bool);
 
+#[derive(Component)]
+pub struct BattleModeText;
+
+#[derive(Component)]
+pub struct DefeatModeText;
+
 // Systems
 // =========================================================================
 
 fn setup_menu (
 	mut commands : Commands,
 	pixel_font : Res<PixelFont>,
+	settings : Res<Settings>,
 ) {
 	commands.spawn((
 		MainMenuUIRoot,
@@ -58,38 +72,104 @@ fn setup_menu (
 			..default()
 		},
 	)).with_children(|parent| {
-		parent.spawn((
-			ButtonBundle {
-				style: Style {
-					margin: UiRect::all(Val::Auto),
-					padding: UiRect::new(
-						Val::Px(30.), Val::Px(30.),
-						Val::Px(10.), Val::Px(10.),
-					),
-					..default()
-				},
-				background_color: Color::hex("6ED57E").unwrap().into(),
+		parent.spawn(NodeBundle {
+			style: Style {
+				margin: UiRect::all(Val::Auto),
+				flex_direction: FlexDirection::Column,
+				align_items: AlignItems::Center,
 				..default()
 			},
-		)).with_children(|parent| {
-			parent.spawn(TextBundle::from_section(
-				"Start Game",
-				TextStyle {
-					font: pixel_font.0.clone(),
-					font_size: 40.,
-					color: Color::WHITE,
+			..default()
+		}).with_children(|parent| {
+			parent.spawn((
+				ButtonBundle {
+					style: Style {
+						margin: UiRect::bottom(Val::Px(20.)),
+						padding: UiRect::new(
+							Val::Px(30.), Val::Px(30.),
+							Val::Px(10.), Val::Px(10.),
+						),
+						..default()
+					},
+					background_color: Color::hex("6ED57E").unwrap().into(),
+					..default()
 				},
-			));
+				Name::new("start"),
+			)).with_children(|parent| {
+				parent.spawn(TextBundle::from_section(
+					"Start Game",
+					TextStyle {
+						font: pixel_font.0.clone(),
+						font_size: 40.,
+						color: Color::WHITE,
+					},
+				));
+			});
+
+			parent.spawn((
+				ButtonBundle {
+					style: Style {
+						padding: UiRect::new(
+							Val::Px(20.), Val::Px(20.),
+							Val::Px(8.), Val::Px(8.),
+						),
+						..default()
+					},
+					background_color: Color::hex("EAB644").unwrap().into(),
+					..default()
+				},
+				Name::new("battle_mode"),
+			)).with_children(|parent| {
+				parent.spawn((
+					TextBundle::from_section(
+						settings.battle_mode.label(),
+						TextStyle {
+							font: pixel_font.0.clone(),
+							font_size: 28.,
+							color: Color::WHITE,
+						},
+					),
+					BattleModeText,
+				));
+			});
+
+			parent.spawn((
+				ButtonBundle {
+					style: Style {
+						margin: UiRect::top(Val::Px(10.)),
+						padding: UiRect::new(
+							Val::Px(20.), Val::Px(20.),
+							Val::Px(8.), Val::Px(8.),
+						),
+						..default()
+					},
+					background_color: Color::hex("D5543B").unwrap().into(),
+					..default()
+				},
+				Name::new("defeat_mode"),
+			)).with_children(|parent| {
+				parent.spawn((
+					TextBundle::from_section(
+						settings.defeat_mode.label(),
+						TextStyle {
+							font: pixel_font.0.clone(),
+							font_size: 28.,
+							color: Color::WHITE,
+						},
+					),
+					DefeatModeText,
+				));
+			});
 		});
 	});
 }
 
 fn on_start_click (
 	mut commands : Commands,
-	interaction_query : Query<(Entity, &Interaction), (Changed<Interaction>, Without<Disabled>)>,
+	interaction_query : Query<(Entity, &Interaction, &Name), (Changed<Interaction>, Without<Disabled>)>,
 ) {
-	for (entity, interaction) in &interaction_query {
-		if interaction == &Interaction::Clicked {
+	for (entity, interaction, name) in &interaction_query {
+		if interaction == &Interaction::Clicked && name.as_str() == "start" {
 			commands.entity(entity).insert(Disabled);
 			create_fadeout(
 				&mut commands,
@@ -99,8 +179,45 @@ fn on_start_click (
 	}
 }
 
+/// Switches between turn based and active time battles
+fn on_battle_mode_click (
+	interaction_query : Query<(&Interaction, &Name), (Changed<Interaction>, Without<Disabled>)>,
+	mut text_query : Query<&mut Text, With<BattleModeText>>,
+	mut settings : ResMut<Settings>,
+) {
+	for (interaction, name) in &interaction_query {
+		if interaction == &Interaction::Clicked && name.as_str() == "battle_mode" {
+			settings.battle_mode = settings.battle_mode.next();
+			text_query.single_mut().sections[0].value = settings.battle_mode.label().to_string();
+		}
+	}
+}
+
+/// Switches between a game over screen and respawning when the party falls
+fn on_defeat_mode_click (
+	interaction_query : Query<(&Interaction, &Name), (Changed<Interaction>, Without<Disabled>)>,
+	mut text_query : Query<&mut Text, With<DefeatModeText>>,
+	mut settings : ResMut<Settings>,
+) {
+	for (interaction, name) in &interaction_query {
+		if interaction == &Interaction::Clicked && name.as_str() == "defeat_mode" {
+			settings.defeat_mode = settings.defeat_mode.next();
+			text_query.single_mut().sections[0].value = settings.defeat_mode.label().to_string();
+		}
+	}
+}
+
 fn set_ui_visibility (is_visible : bool) -> impl Fn(Query<&mut Visibility, With<MainMenuUIRoot>>) {
 	move |
 		mut query : Query<&mut Visibility, With<MainMenuUIRoot>>,
 	| { query.single_mut().is_visible = is_visible; }
 }
+
+fn despawn_menu (
+	mut commands : Commands,
+	query : Query<Entity, With<MainMenuUIRoot>>,
+) {
+	for id in &query {
+		commands.entity(id).despawn_recursive();
+	}
+}
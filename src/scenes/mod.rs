@@ -1,7 +1,9 @@
+pub mod game_over;
 pub mod main_menu;
 pub mod overworld;
 
 use bevy::prelude::*;
+use crate::scenes::game_over::GameOverPlugin;
 use crate::scenes::main_menu::MainMenuPlugin;
 use crate::scenes::overworld::OverworldPlugin;
 
@@ -12,6 +14,7 @@ impl Plugin for ScenesPlugin {
 		app
 			.add_plugin(MainMenuPlugin)
 			.add_plugin(OverworldPlugin)
+			.add_plugin(GameOverPlugin)
 		;
 	}
 }
@@ -1,7 +1,14 @@
 use bevy::prelude::*;
 use crate::core::assets::Tilesheet;
-use crate::core::tilemap::create_simple_map;
+use crate::core::tilemap::{ActiveMap, create_simple_map, load_map, Map, MapAsset, TeleportEvent, WarpEvent};
+use crate::core::tilemap::autotile::AutotileSet;
+use crate::core::tilemap::grid::MapGrid;
 use crate::GameState;
+use crate::npc::NpcBubble;
+use crate::player::{move_player_to_spawn, Player};
+
+/// The map a new game starts on
+pub const STARTING_MAP : &str = "test";
 
 // Plugin
 // =========================================================================
@@ -27,6 +34,9 @@ impl Plugin for OverworldPlugin {
 				SystemSet::on_exit(GameState::Overworld)
 					.with_system(despawn_scene)
 			)
+			.add_system(sync_map)
+			.add_system(handle_warps)
+			.add_system(handle_teleports)
 		;
 	}
 }
@@ -42,13 +52,9 @@ pub struct SceneOverworld;
 
 fn spawn_scene (
 	mut commands : Commands,
-	tilesheet : Res<Tilesheet>,
+	assets : Res<AssetServer>,
 ) {
-	let map = create_simple_map(
-		"test",
-		&mut commands,
-		tilesheet,
-	);
+	commands.insert_resource(ActiveMap(load_map(STARTING_MAP, &assets)));
 
 	commands.spawn((
 		SceneOverworld,
@@ -56,7 +62,7 @@ fn spawn_scene (
 		GlobalTransform::default(),
 		Visibility::default(),
 		ComputedVisibility::default(),
-	)).push_children(&[map]);
+	));
 }
 
 fn despawn_scene (
@@ -64,6 +70,48 @@ fn despawn_scene (
 	query : Query<Entity, With<SceneOverworld>>,
 ) {
 	commands.entity(query.single()).despawn_recursive();
+	commands.remove_resource::<ActiveMap>();
+	commands.remove_resource::<MapGrid>();
+}
+
+/// Spawns the active map once it has loaded, and respawns it whenever the
+/// map file or the autotile rules change on disk
+#[allow(clippy::too_many_arguments)]
+fn sync_map (
+	mut commands : Commands,
+	mut map_events : EventReader<AssetEvent<MapAsset>>,
+	mut autotile_events : EventReader<AssetEvent<AutotileSet>>,
+	maps : Res<Assets<MapAsset>>,
+	tilesheet : Res<Tilesheet>,
+	active_map : Option<Res<ActiveMap>>,
+	scene_query : Query<Entity, With<SceneOverworld>>,
+	map_query : Query<Entity, With<Map>>,
+	bubble_query : Query<Entity, With<NpcBubble>>,
+) {
+	let autotiles_changed = autotile_events
+		.iter()
+		.any(|event| matches!(event, AssetEvent::Modified { .. }));
+
+	let Some(active_map) = active_map else {
+		map_events.clear();
+		return;
+	};
+
+	let map_changed = map_events.iter().any(|event| match event {
+		AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle == &active_map.0,
+		_ => false,
+	});
+	let changed = map_changed || autotiles_changed;
+
+	let Ok(scene) = scene_query.get_single() else { return };
+
+	if !changed && !map_query.is_empty() { return; }
+	let Some(map) = maps.get(&active_map.0) else { return };
+
+	despawn_maps(&mut commands, &map_query, &bubble_query);
+
+	let map = create_simple_map(map, &mut commands, &tilesheet);
+	commands.entity(scene).push_children(&[map]);
 }
 
 fn show_scene (
@@ -77,3 +125,68 @@ fn hide_scene (
 ) {
 	query.single_mut().is_visible = false;
 }
+
+fn handle_warps (
+	mut commands : Commands,
+	mut warp_events : EventReader<WarpEvent>,
+	assets : Res<AssetServer>,
+	map_query : Query<Entity, With<Map>>,
+	bubble_query : Query<Entity, With<NpcBubble>>,
+) {
+	let Some(WarpEvent(warp)) = warp_events.iter().last() else { return };
+
+	swap_map(&mut commands, load_map(&warp.map, &assets), &map_query, &bubble_query);
+	move_player_to_spawn(&mut commands, &warp.spawn);
+}
+
+fn handle_teleports (
+	mut commands : Commands,
+	mut teleport_events : EventReader<TeleportEvent>,
+	active_map : Option<Res<ActiveMap>>,
+	mut player_query : Query<&mut Transform, With<Player>>,
+	map_query : Query<Entity, With<Map>>,
+	bubble_query : Query<Entity, With<NpcBubble>>,
+) {
+	let Some(teleport) = teleport_events.iter().last() else { return };
+
+	if active_map.is_none_or(|active_map| active_map.0 != teleport.map) {
+		swap_map(&mut commands, teleport.map.clone(), &map_query, &bubble_query);
+	}
+
+	if let Ok(mut transform) = player_query.get_single_mut() {
+		transform.translation.x = teleport.position.x;
+		transform.translation.y = teleport.position.y;
+	}
+}
+
+// Helpers
+// =========================================================================
+
+/// Takes down the current map and makes `map` the one to show, which is
+/// spawned once it has loaded
+fn swap_map (
+	commands : &mut Commands,
+	map : Handle<MapAsset>,
+	map_query : &Query<Entity, With<Map>>,
+	bubble_query : &Query<Entity, With<NpcBubble>>,
+) {
+	despawn_maps(commands, map_query, bubble_query);
+	commands.remove_resource::<MapGrid>();
+	commands.insert_resource(ActiveMap(map));
+}
+
+fn despawn_maps (
+	commands : &mut Commands,
+	map_query : &Query<Entity, With<Map>>,
+	bubble_query : &Query<Entity, With<NpcBubble>>,
+) {
+	// The NPC bubble gets parented to NPC tiles, so pull it out before the
+	// old tiles go
+	for bubble in bubble_query {
+		commands.entity(bubble).remove_parent();
+	}
+
+	for map in map_query {
+		commands.entity(map).despawn_recursive();
+	}
+}